@@ -1,5 +1,7 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use zoc::{search::range, Bbox, Z};
+#![allow(deprecated)] // criterion::black_box
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use zoc::{search::{range, scan, Eytzinger}, sort::radix_sort, Bbox, Z};
 
 criterion_group!(benches, benchmark);
 criterion_main!(benches);
@@ -168,4 +170,93 @@ fn benchmark(c: &mut Criterion) {
         }));
     }
     group.finish();
+
+    let uniform = {
+        let mut vec = (0 .. 100_000).map(|_| Z::from(rand::random::<[u16; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+        vec
+    };
+
+    let clustered = {
+        let mut vec = Vec::new();
+        for _ in 0 .. 20 {
+            let [cx, cy] = rand::random::<[u16; 2]>();
+            for _ in 0 .. 5000 {
+                let [dx, dy] = rand::random::<[u8; 2]>();
+                vec.push(Z::from([cx.wrapping_add(dx.into()), cy.wrapping_add(dy.into())]))
+            }
+        }
+        vec.sort_unstable();
+        vec
+    };
+
+    let boxes = (0 .. 100)
+        .map(|_| {
+            let [x, y] = rand::random::<[u16; 2]>();
+            let [w, h] = rand::random::<[u16; 2]>().map(|n| n / 8);
+            ([x, y], [x.saturating_add(w), y.saturating_add(h)])
+        })
+        .collect::<Vec<_>>();
+    let small = (0 .. 100)
+        .map(|_| {
+            let [x, y] = rand::random::<[u16; 2]>();
+            let [w, h] = rand::random::<[u16; 2]>().map(|n| n / 512);
+            ([x, y], [x.saturating_add(w), y.saturating_add(h)])
+        })
+        .collect::<Vec<_>>();
+
+    for (name, vec) in [("search uniform", &uniform), ("search clustered", &clustered)] {
+        let mut group = c.benchmark_group(name);
+        for t in [0, 4, 10, 32] {
+            group.bench_with_input(BenchmarkId::from_parameter(t), &t, |b, &t| b.iter(|| {
+                for (min, max) in &boxes {
+                    black_box(range(vec.as_slice(), *min, *max).optimize_if_gt(t).count());
+                }
+            }));
+        }
+        group.bench_function("adaptive", |b| b.iter(|| {
+            for (min, max) in &boxes {
+                black_box(range(vec.as_slice(), *min, *max).optimize_adaptive().count());
+            }
+        }));
+        group.bench_function("skip-scan", |b| b.iter(|| {
+            for (min, max) in &boxes {
+                black_box(scan(vec.as_slice(), *min, *max).count());
+            }
+        }));
+        group.bench_function("small boxes", |b| b.iter(|| {
+            for (min, max) in &small {
+                black_box(range(vec.as_slice(), *min, *max).count());
+            }
+        }));
+        group.bench_function("skip-scan small boxes", |b| b.iter(|| {
+            for (min, max) in &small {
+                black_box(scan(vec.as_slice(), *min, *max).count());
+            }
+        }));
+        let index = Eytzinger::new(vec.clone());
+        group.bench_function("eytzinger", |b| b.iter(|| {
+            for (min, max) in &boxes {
+                black_box(index.range(*min, *max).count());
+            }
+        }));
+        group.finish();
+    }
+
+    let u64s = (0 .. 1_000_000).map(|i| (Z::from(rand::random::<[u32; 2]>()), i)).collect::<Vec<_>>();
+    let u128s = (0 .. 1_000_000).map(|i| (Z::from(rand::random::<[u32; 4]>()), i)).collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("sort");
+    group.sample_size(10);
+    group.bench_function("sort_unstable [u32; 2]", |b| b.iter_batched_ref(|| u64s.clone(), |v| v.sort_unstable_by_key(|(z, _)| *z), BatchSize::LargeInput));
+    group.bench_function("radix_sort [u32; 2]", |b| b.iter_batched_ref(|| u64s.clone(), |v| radix_sort(v), BatchSize::LargeInput));
+    group.bench_function("sort_unstable [u32; 4]", |b| b.iter_batched_ref(|| u128s.clone(), |v| v.sort_unstable_by_key(|(z, _)| *z), BatchSize::LargeInput));
+    group.bench_function("radix_sort [u32; 4]", |b| b.iter_batched_ref(|| u128s.clone(), |v| radix_sort(v), BatchSize::LargeInput));
+    #[cfg(feature = "rayon")]
+    {
+        use zoc::sort::par_radix_sort;
+        group.bench_function("par_radix_sort [u32; 2]", |b| b.iter_batched_ref(|| u64s.clone(), |v| par_radix_sort(v), BatchSize::LargeInput));
+        group.bench_function("par_radix_sort [u32; 4]", |b| b.iter_batched_ref(|| u128s.clone(), |v| par_radix_sort(v), BatchSize::LargeInput));
+    }
+    group.finish();
 }
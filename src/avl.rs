@@ -0,0 +1,299 @@
+//! An AVL tree map keyed by `Z` values.
+
+use alloc::boxed::Box;
+use core::cmp::Ordering;
+use crate::{Size, Z};
+use crate::search::{range_tree, TreeNode, TreeRange};
+
+/// A map from `Z` values to values, stored in an AVL tree.
+///
+/// The heights of the two subtrees of any node differ by at most one, which
+/// keeps insertion, removal and lookup logarithmic in the number of entries.
+/// Range searches traverse the tree with [`range_tree`].
+pub struct AvlTree<const D: usize, T: Size<D>, V> {
+    root: Link<D, T, V>,
+    len: usize
+}
+
+type Link<const D: usize, T, V> = Option<Box<AvlNode<D, T, V>>>;
+
+/// A node of an [`AvlTree`].
+pub struct AvlNode<const D: usize, T: Size<D>, V> {
+    z: Z<D, T>,
+    value: V,
+    height: u8,
+    left: Link<D, T, V>,
+    right: Link<D, T, V>
+}
+
+impl<const D: usize, T: Size<D>, V> Default for AvlTree<D, T, V> {
+    fn default() -> Self {
+        AvlTree { root: None, len: 0 }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> AvlTree<D, T, V> {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the root node, if any.
+    pub fn root(&self) -> Option<&AvlNode<D, T, V>> {
+        self.root.as_deref()
+    }
+
+    /// Get the value of the given `Z` value.
+    pub fn get(&self, z: &Z<D, T>) -> Option<&V> {
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            match z.cmp(&n.z) {
+                | Ordering::Less    => node = n.left.as_deref(),
+                | Ordering::Greater => node = n.right.as_deref(),
+                | Ordering::Equal   => return Some(&n.value)
+            }
+        }
+        None
+    }
+
+    /// Insert a value, returning the previous value of the same `Z` value.
+    pub fn insert(&mut self, z: Z<D, T>, value: V) -> Option<V> {
+        let (root, old) = insert(self.root.take(), z, value);
+        self.root = Some(root);
+        if old.is_none() {
+            self.len += 1
+        }
+        old
+    }
+
+    /// Remove the value of the given `Z` value.
+    pub fn remove(&mut self, z: &Z<D, T>) -> Option<V> {
+        let (root, old) = remove(self.root.take(), z);
+        self.root = root;
+        if old.is_some() {
+            self.len -= 1
+        }
+        old
+    }
+
+    /// Iterate over the nodes within the bounding box.
+    ///
+    /// The nodes are returned in ascending `Z` order, or in descending order
+    /// when iterating from the back.
+    pub fn range(&self, min: [T; D], max: [T; D]) -> TreeRange<'_, D, T, AvlNode<D, T, V>> {
+        range_tree(self.root(), min, max)
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FromIterator<(Z<D, T>, V)> for AvlTree<D, T, V> {
+    fn from_iter<I: IntoIterator<Item = (Z<D, T>, V)>>(iter: I) -> Self {
+        let mut tree = AvlTree::new();
+        for (z, v) in iter {
+            tree.insert(z, v);
+        }
+        tree
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> AvlNode<D, T, V> {
+    /// Get the `Z` value of this node.
+    pub fn z(&self) -> &Z<D, T> {
+        &self.z
+    }
+
+    /// Get the value of this node.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn leaf(z: Z<D, T>, value: V) -> Box<Self> {
+        Box::new(AvlNode { z, value, height: 1, left: None, right: None })
+    }
+
+    /// Recompute the height from the subtrees.
+    fn update(&mut self) {
+        self.height = 1 + height(&self.left).max(height(&self.right))
+    }
+
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
+        let mut r = self.right.take().expect("rotated node has a right child");
+        self.right = r.left.take();
+        self.update();
+        r.left = Some(self);
+        r.update();
+        r
+    }
+
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
+        let mut l = self.left.take().expect("rotated node has a left child");
+        self.left = l.right.take();
+        self.update();
+        l.right = Some(self);
+        l.update();
+        l
+    }
+
+    /// Restore the balance of a node whose subtrees differ in height by at most two.
+    fn rebalance(mut self: Box<Self>) -> Box<Self> {
+        self.update();
+        let (hl, hr) = (height(&self.left), height(&self.right));
+        if hl > hr + 1 {
+            if let Some(l) = self.left.take() {
+                self.left = Some(if height(&l.left) < height(&l.right) { l.rotate_left() } else { l })
+            }
+            self.rotate_right()
+        } else if hr > hl + 1 {
+            if let Some(r) = self.right.take() {
+                self.right = Some(if height(&r.right) < height(&r.left) { r.rotate_right() } else { r })
+            }
+            self.rotate_left()
+        } else {
+            self
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> TreeNode<D, T> for AvlNode<D, T, V> {
+    fn key(&self) -> Z<D, T> {
+        self.z
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn height<const D: usize, T: Size<D>, V>(link: &Link<D, T, V>) -> u8 {
+    link.as_ref().map_or(0, |n| n.height)
+}
+
+fn insert<const D: usize, T: Size<D>, V>(link: Link<D, T, V>, z: Z<D, T>, value: V) -> (Box<AvlNode<D, T, V>>, Option<V>) {
+    let Some(mut n) = link else {
+        return (AvlNode::leaf(z, value), None)
+    };
+    match z.cmp(&n.z) {
+        | Ordering::Less => {
+            let (l, old) = insert(n.left.take(), z, value);
+            n.left = Some(l);
+            (n.rebalance(), old)
+        }
+        | Ordering::Greater => {
+            let (r, old) = insert(n.right.take(), z, value);
+            n.right = Some(r);
+            (n.rebalance(), old)
+        }
+        | Ordering::Equal => {
+            let old = core::mem::replace(&mut n.value, value);
+            (n, Some(old))
+        }
+    }
+}
+
+fn remove<const D: usize, T: Size<D>, V>(link: Link<D, T, V>, z: &Z<D, T>) -> (Link<D, T, V>, Option<V>) {
+    let Some(mut n) = link else {
+        return (None, None)
+    };
+    match z.cmp(&n.z) {
+        | Ordering::Less => {
+            let (l, old) = remove(n.left.take(), z);
+            n.left = l;
+            (Some(n.rebalance()), old)
+        }
+        | Ordering::Greater => {
+            let (r, old) = remove(n.right.take(), z);
+            n.right = r;
+            (Some(n.rebalance()), old)
+        }
+        | Ordering::Equal => {
+            let n = *n;
+            match (n.left, n.right) {
+                | (None, r)    => (r, Some(n.value)),
+                | (l, None)    => (l, Some(n.value)),
+                | (l, Some(r)) => {
+                    let (r, mut m) = remove_min(r);
+                    m.left  = l;
+                    m.right = r;
+                    (Some(m.rebalance()), Some(n.value))
+                }
+            }
+        }
+    }
+}
+
+/// Remove the node with the smallest `Z` value from a subtree.
+///
+/// Returns the remaining subtree and the removed node.
+fn remove_min<const D: usize, T: Size<D>, V>(mut n: Box<AvlNode<D, T, V>>) -> (Link<D, T, V>, Box<AvlNode<D, T, V>>) {
+    match n.left.take() {
+        | Some(l) => {
+            let (l, min) = remove_min(l);
+            n.left = l;
+            (Some(n.rebalance()), min)
+        }
+        | None => (n.right.take(), n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::{AvlNode, AvlTree};
+
+    /// Check the ordering and balance of a subtree and return its height.
+    fn check(n: Option<&AvlNode<2, u8, u32>>, min: Option<Z<2, u8>>, max: Option<Z<2, u8>>) -> u8 {
+        let Some(n) = n else {
+            return 0
+        };
+        assert!(min.is_none_or(|m| m < n.z));
+        assert!(max.is_none_or(|m| m > n.z));
+        let hl = check(n.left.as_deref(), min, Some(n.z));
+        let hr = check(n.right.as_deref(), Some(n.z), max);
+        assert!(hl.abs_diff(hr) <= 1);
+        assert_eq!(n.height, 1 + hl.max(hr));
+        n.height
+    }
+
+    #[test]
+    fn avl_tree() {
+        let mut tree = AvlTree::new();
+        let mut map = BTreeMap::new();
+        for i in 0 .. 10_000 {
+            let z = Z::from(rand::random::<[u8; 2]>());
+            if rand::random::<u8>() < 64 {
+                assert_eq!(map.remove(&z), tree.remove(&z))
+            } else {
+                assert_eq!(map.insert(z, i), tree.insert(z, i))
+            }
+            assert_eq!(map.len(), tree.len())
+        }
+        check(tree.root(), None, None);
+        for (z, v) in &map {
+            assert_eq!(Some(v), tree.get(z))
+        }
+
+        let vec = map.into_iter().collect::<Vec<_>>();
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).copied().collect::<Vec<_>>();
+            let res = tree.range(min, max).map(|n| (*n.z(), *n.value())).collect::<Vec<_>>();
+            assert_eq!(expected, res)
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Mutable indices of values keyed by `Z` values.
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::Range;
+use crate::{Bbox, Size, GetZ, Z};
+use crate::search::{self, Zrange};
+
+mod lsm;
+#[cfg(feature = "std")]
+mod shared;
+
+pub use lsm::{LsmIndex, LsmRange};
+#[cfg(feature = "std")]
+pub use shared::{SharedIndex, Snapshot, SnapshotRange};
+
+/// How to handle entries with equal `Z` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Duplicates {
+    /// Keep all entries, in the order of insertion.
+    Keep,
+    /// Replace an existing entry.
+    #[default]
+    Replace,
+    /// Keep an existing entry and discard the new one.
+    Ignore
+}
+
+/// A mutable map from `Z` values to values.
+///
+/// Entries are kept in a vector sorted by `Z` value, so that box queries
+/// can use [`search::range`] directly. Whether several entries may have the
+/// same `Z` value is determined by the [`Duplicates`] policy.
+#[derive(Clone)]
+pub struct ZIndex<const D: usize, T: Size<D>, V> {
+    items: Vec<(Z<D, T>, V)>,
+    duplicates: Duplicates
+}
+
+impl<const D: usize, T: Size<D>, V> Default for ZIndex<D, T, V> {
+    fn default() -> Self {
+        ZIndex { items: Vec::new(), duplicates: Duplicates::default() }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> ZIndex<D, T, V> {
+    /// Create an empty index which replaces entries with equal `Z` values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty index with the given duplicates policy.
+    pub fn with_duplicates(duplicates: Duplicates) -> Self {
+        ZIndex { items: Vec::new(), duplicates }
+    }
+
+    /// Get the duplicates policy.
+    pub fn duplicates(&self) -> Duplicates {
+        self.duplicates
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Borrow the entries in ascending `Z` order.
+    pub fn as_slice(&self) -> &[(Z<D, T>, V)] {
+        &self.items
+    }
+
+    /// Get back the entries in ascending `Z` order.
+    pub fn into_vec(self) -> Vec<(Z<D, T>, V)> {
+        self.items
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self) {
+        self.items.clear()
+    }
+
+    /// Get the positions of all entries with the given `Z` value.
+    fn positions(&self, z: &Z<D, T>) -> Range<usize> {
+        let start = self.items.partition_point(|(k, _)| k < z);
+        let end = start + self.items[start ..].partition_point(|(k, _)| k == z);
+        start .. end
+    }
+
+    /// Get the value of the first entry with the given `Z` value.
+    pub fn get(&self, z: &Z<D, T>) -> Option<&V> {
+        let i = self.positions(z).next()?;
+        Some(&self.items[i].1)
+    }
+
+    /// Get the value of the first entry with the given `Z` value mutably.
+    pub fn get_mut(&mut self, z: &Z<D, T>) -> Option<&mut V> {
+        let i = self.positions(z).next()?;
+        Some(&mut self.items[i].1)
+    }
+
+    /// Get the values of all entries with the given `Z` value.
+    pub fn get_all(&self, z: &Z<D, T>) -> impl DoubleEndedIterator<Item = &V> + '_ {
+        self.items[self.positions(z)].iter().map(|(_, v)| v)
+    }
+
+    /// Check if there is an entry with the given `Z` value.
+    pub fn contains(&self, z: &Z<D, T>) -> bool {
+        !self.positions(z).is_empty()
+    }
+
+    /// Insert an entry.
+    ///
+    /// Returns the value which is not stored in the index because of the
+    /// duplicates policy, i.e. the replaced value with [`Duplicates::Replace`]
+    /// or the given value with [`Duplicates::Ignore`].
+    pub fn insert(&mut self, z: Z<D, T>, value: V) -> Option<V> {
+        let r = self.positions(&z);
+        match self.duplicates {
+            | _ if r.is_empty()   => {
+                self.items.insert(r.start, (z, value));
+                None
+            }
+            | Duplicates::Keep    => {
+                self.items.insert(r.end, (z, value));
+                None
+            }
+            | Duplicates::Replace => Some(mem::replace(&mut self.items[r.start].1, value)),
+            | Duplicates::Ignore  => Some(value)
+        }
+    }
+
+    /// Remove the first entry with the given `Z` value.
+    pub fn remove(&mut self, z: &Z<D, T>) -> Option<V> {
+        let i = self.positions(z).next()?;
+        Some(self.items.remove(i).1)
+    }
+
+    /// Remove all entries with the given `Z` value.
+    pub fn remove_all(&mut self, z: &Z<D, T>) -> Vec<V> {
+        let r = self.positions(z);
+        self.items.drain(r).map(|(_, v)| v).collect()
+    }
+
+    /// Move the first entry with the `Z` value `from` to `to`.
+    ///
+    /// The entry is re-inserted according to the duplicates policy and the
+    /// value which is not stored in the index is returned (cf. [`ZIndex::insert`]).
+    /// Returns `Err(*from)` if there is no entry at `from`.
+    pub fn update(&mut self, from: &Z<D, T>, to: Z<D, T>) -> Result<Option<V>, Z<D, T>> {
+        let value = self.remove(from).ok_or(*from)?;
+        Ok(self.insert(to, value))
+    }
+
+    /// Iterate over the entries within the bounding box.
+    ///
+    /// The entries are returned in ascending `Z` order, or in descending order
+    /// when iterating from the back.
+    pub fn range(&self, min: [T; D], max: [T; D]) -> Zrange<'_, '_, D, T, (Z<D, T>, V)> {
+        search::range(&self.items, min, max)
+    }
+
+    /// Get the index ranges of all entries within the bounding box.
+    fn runs(&self, bbox: &Bbox<D, T>) -> Vec<Range<usize>> {
+        search::range(&self.items, *bbox.min_parts(), *bbox.max_parts()).runs().collect()
+    }
+
+    /// Retain only the entries within the bounding box for which the predicate holds.
+    ///
+    /// Entries outside of the bounding box are kept.
+    pub fn retain_in<F>(&mut self, bbox: &Bbox<D, T>, mut f: F)
+    where
+        F: FnMut(&Z<D, T>, &mut V) -> bool
+    {
+        let mut runs = self.runs(bbox).into_iter().flatten().peekable();
+        let mut i = 0;
+        self.items.retain_mut(|(z, v)| {
+            let keep = if runs.next_if_eq(&i).is_some() { f(z, v) } else { true };
+            i += 1;
+            keep
+        })
+    }
+
+    /// Remove all entries within the bounding box.
+    ///
+    /// The removed entries are returned in ascending `Z` order.
+    pub fn drain_in(&mut self, bbox: &Bbox<D, T>) -> Vec<(Z<D, T>, V)> {
+        let runs = self.runs(bbox);
+        let n = runs.iter().map(|r| r.len()).sum();
+        let mut drained = Vec::with_capacity(n);
+        let mut kept = Vec::with_capacity(self.items.len() - n);
+        let mut runs = runs.into_iter().flatten().peekable();
+        for (i, e) in mem::take(&mut self.items).into_iter().enumerate() {
+            if runs.next_if_eq(&i).is_some() {
+                drained.push(e)
+            } else {
+                kept.push(e)
+            }
+        }
+        self.items = kept;
+        drained
+    }
+
+    /// Sort the entries after appending new ones from position `start` on.
+    ///
+    /// The sort is stable, so entries with equal `Z` values remain in the
+    /// order of insertion and duplicates can be resolved accordingly. As the
+    /// existing entries are already sorted, they form a single run which the
+    /// sort merges with the new entries.
+    fn restore(&mut self, start: usize) {
+        if start == self.items.len() {
+            return
+        }
+        self.items.sort_by_key(|(z, _)| *z);
+        match self.duplicates {
+            | Duplicates::Keep    => {}
+            | Duplicates::Ignore  => self.items.dedup_by(|a, b| a.0 == b.0),
+            | Duplicates::Replace => dedup_last(&mut self.items)
+        }
+    }
+}
+
+/// Remove consecutive entries with equal `Z` values, keeping the last one.
+fn dedup_last<const D: usize, T: Size<D>, A: GetZ<D, T>>(items: &mut Vec<A>) {
+    items.dedup_by(|a, b| {
+        // `b` precedes `a` and is retained, so move the later entry there.
+        if a.z() == b.z() {
+            mem::swap(a, b);
+            true
+        } else {
+            false
+        }
+    })
+}
+
+/// Bulk load entries in any order.
+///
+/// The new entries are sorted and merged with the existing ones in one go,
+/// which is much faster than inserting them one by one.
+impl<const D: usize, T: Size<D>, V> Extend<(Z<D, T>, V)> for ZIndex<D, T, V> {
+    fn extend<I: IntoIterator<Item = (Z<D, T>, V)>>(&mut self, iter: I) {
+        let start = self.items.len();
+        self.items.extend(iter);
+        self.restore(start)
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FromIterator<(Z<D, T>, V)> for ZIndex<D, T, V> {
+    fn from_iter<I: IntoIterator<Item = (Z<D, T>, V)>>(iter: I) -> Self {
+        let mut index = ZIndex::new();
+        index.extend(iter);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+    use crate::{search, Bbox, Z};
+    use super::{Duplicates, ZIndex};
+
+    #[test]
+    fn insert_remove() {
+        let mut index = ZIndex::new();
+        let mut map = BTreeMap::new();
+        for i in 0 .. 5000 {
+            let z = Z::from(rand::random::<[u8; 2]>());
+            if rand::random::<u8>() < 64 {
+                assert_eq!(map.remove(&z), index.remove(&z))
+            } else {
+                assert_eq!(map.insert(z, i), index.insert(z, i))
+            }
+        }
+        assert_eq!(map.clone().into_iter().collect::<Vec<_>>(), index.as_slice());
+
+        let z = *map.keys().next().unwrap();
+        let to = Z::from([0, 0]);
+        let v = map.remove(&z).unwrap();
+        assert_eq!(Ok(map.insert(to, v)), index.update(&z, to));
+        let missing = (0 ..= 255).map(|x| Z::from([x, 255])).find(|z| !map.contains_key(z)).unwrap();
+        assert_eq!(Err(missing), index.update(&missing, to));
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), index.as_slice())
+    }
+
+    #[test]
+    fn duplicates() {
+        let z = Z::<2, u8>::from([1, 2]);
+        for (d, expected) in [(Duplicates::Keep, &[1, 2, 3][..]), (Duplicates::Replace, &[3]), (Duplicates::Ignore, &[1])] {
+            let mut index = ZIndex::with_duplicates(d);
+            index.insert(z, 1);
+            index.extend([(z, 2), (Z::from([0, 0]), 0)]);
+            index.insert(z, 3);
+            assert_eq!(expected, index.get_all(&z).copied().collect::<Vec<_>>());
+            assert_eq!(Some(&expected[0]), index.get(&z));
+            assert_eq!(expected, index.remove_all(&z));
+            assert!(!index.contains(&z))
+        }
+    }
+
+    #[test]
+    fn bulk_load() {
+        let entries = (0 .. 5000).map(|i| (Z::from(rand::random::<[u8; 2]>()), i)).collect::<Vec<_>>();
+        let map = entries.iter().copied().collect::<BTreeMap<_, _>>();
+        let mut index = entries[.. 2000].iter().copied().collect::<ZIndex<_, _, _>>();
+        index.extend(entries[2000 ..].iter().copied());
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), index.as_slice())
+    }
+
+    #[test]
+    fn retain_drain() {
+        let mut index = (0 .. 5000).map(|i| (Z::from(rand::random::<[u8; 2]>()), i)).collect::<ZIndex<_, _, _>>();
+        for _ in 0 .. 20 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let bbox = Bbox::new(Z::from(min), Z::from(max));
+            let vec = index.as_slice().to_vec();
+
+            let mut expected = vec.clone();
+            expected.retain(|(z, v)| !bbox.contains(z) || v % 2 == 0);
+            index.retain_in(&bbox, |_, v| *v % 2 == 0);
+            assert_eq!(expected, index.as_slice());
+
+            let drained = search::range(index.as_slice(), min, max).copied().collect::<Vec<_>>();
+            let mut expected = index.as_slice().to_vec();
+            expected.retain(|(z, _)| !bbox.contains(z));
+            assert_eq!(drained, index.drain_in(&bbox));
+            assert_eq!(expected, index.as_slice());
+            assert_eq!(0, index.range(min, max).count());
+            index.extend(vec.into_iter().filter(|(z, _)| bbox.contains(z)))
+        }
+    }
+}
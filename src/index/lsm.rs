@@ -0,0 +1,294 @@
+use alloc::vec::{self, Vec};
+use core::iter::{FusedIterator, Peekable};
+use core::mem;
+use crate::{Bbox, Size, GetZ, Z};
+use crate::search::{self, Zrange};
+use super::dedup_last;
+
+/// An entry of a log-structured index, where `None` marks a deletion.
+type Entry<const D: usize, T, V> = (Z<D, T>, Option<V>);
+
+/// A sorted run of entries with distinct `Z` values.
+#[derive(Clone)]
+struct Run<const D: usize, T: Size<D>, V> {
+    /// The number of merges this run is the result of.
+    level: u32,
+    entries: Vec<Entry<D, T, V>>
+}
+
+/// A write-optimised map from `Z` values to values.
+///
+/// New entries and deletions (tombstones) are appended to an unsorted
+/// buffer. When the buffer is full, it is sorted into a new run. Whenever
+/// `fanout` runs of the same level exist, they are merged into one run of
+/// the next level, so that inserts never shift large sorted arrays and the
+/// number of runs grows only logarithmically. Newer entries shadow older
+/// ones with the same `Z` value.
+#[derive(Clone)]
+pub struct LsmIndex<const D: usize, T: Size<D>, V> {
+    buffer: Vec<Entry<D, T, V>>,
+    /// The runs from oldest to newest.
+    runs: Vec<Run<D, T, V>>,
+    buffer_len: usize,
+    fanout: usize
+}
+
+impl<const D: usize, T: Size<D>, V> Default for LsmIndex<D, T, V> {
+    fn default() -> Self {
+        LsmIndex {
+            buffer: Vec::new(),
+            runs: Vec::new(),
+            buffer_len: 1024,
+            fanout: 4
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> LsmIndex<D, T, V> {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of entries buffered before a new run is created (default = 1024).
+    pub fn buffer_len(mut self, n: usize) -> Self {
+        self.buffer_len = n.max(1);
+        self
+    }
+
+    /// Set the number of runs of the same level which are merged (default = 4).
+    pub fn fanout(mut self, n: usize) -> Self {
+        self.fanout = n.max(2);
+        self
+    }
+
+    /// Get the number of sorted runs.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Insert an entry, replacing any entry with the same `Z` value.
+    pub fn insert(&mut self, z: Z<D, T>, value: V) {
+        self.push((z, Some(value)))
+    }
+
+    /// Remove the entry with the given `Z` value, if any.
+    pub fn remove(&mut self, z: &Z<D, T>) {
+        self.push((*z, None))
+    }
+
+    fn push(&mut self, e: Entry<D, T, V>) {
+        self.buffer.push(e);
+        if self.buffer.len() >= self.buffer_len {
+            self.flush()
+        }
+    }
+
+    /// Get the value of the given `Z` value.
+    pub fn get(&self, z: &Z<D, T>) -> Option<&V> {
+        if let Some((_, v)) = self.buffer.iter().rfind(|(k, _)| k == z) {
+            return v.as_ref()
+        }
+        for r in self.runs.iter().rev() {
+            if let Ok(i) = r.entries.binary_search_by(|(k, _)| k.cmp(z)) {
+                return r.entries[i].1.as_ref()
+            }
+        }
+        None
+    }
+
+    /// Sort the buffered entries into a new run and merge runs as necessary.
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return
+        }
+        let mut entries = mem::take(&mut self.buffer);
+        entries.sort_by_key(|(z, _)| *z);
+        dedup_last(&mut entries);
+        self.runs.push(Run { level: 0, entries });
+        while self.runs.len() >= self.fanout {
+            let n = self.runs.len();
+            let level = self.runs[n - 1].level;
+            if self.runs[n - self.fanout ..].iter().any(|r| r.level != level) {
+                break
+            }
+            let runs = self.runs.split_off(n - self.fanout);
+            let purge = self.runs.is_empty();
+            let entries = merge(runs, purge);
+            if !entries.is_empty() {
+                self.runs.push(Run { level: level + 1, entries })
+            }
+        }
+    }
+
+    /// Merge the buffer and all runs into a single run without tombstones.
+    pub fn compact(&mut self) {
+        self.flush();
+        if self.runs.len() > 1 || self.runs.iter().any(|r| r.entries.iter().any(|(_, v)| v.is_none())) {
+            let level = self.runs.iter().map(|r| r.level).max().unwrap_or(0);
+            let entries = merge(mem::take(&mut self.runs), true);
+            if !entries.is_empty() {
+                self.runs.push(Run { level, entries })
+            }
+        }
+    }
+
+    /// Iterate over the entries within the bounding box.
+    ///
+    /// Every run is searched like a slice with [`search::range`] and the
+    /// results are merged. Entries are returned in ascending `Z` order.
+    pub fn range(&self, min: [T; D], max: [T; D]) -> LsmRange<'_, D, T, V> {
+        let bbox = Bbox::new(min.into(), max.into());
+        let mut buffered = self.buffer.iter().filter(|(z, _)| bbox.contains(z)).collect::<Vec<_>>();
+        buffered.sort_by_key(|(z, _)| *z);
+        dedup_last(&mut buffered);
+        let mut sources = Vec::with_capacity(self.runs.len() + 1);
+        sources.push(Source::Buffer(buffered.into_iter()).peekable());
+        for r in self.runs.iter().rev() {
+            sources.push(Source::Run(search::range(&r.entries, min, max)).peekable())
+        }
+        LsmRange { sources }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> Extend<(Z<D, T>, V)> for LsmIndex<D, T, V> {
+    fn extend<I: IntoIterator<Item = (Z<D, T>, V)>>(&mut self, iter: I) {
+        for (z, v) in iter {
+            self.insert(z, v)
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FromIterator<(Z<D, T>, V)> for LsmIndex<D, T, V> {
+    fn from_iter<I: IntoIterator<Item = (Z<D, T>, V)>>(iter: I) -> Self {
+        let mut index = LsmIndex::new();
+        index.extend(iter);
+        index
+    }
+}
+
+/// Merge runs given from oldest to newest into one.
+///
+/// Of several entries with the same `Z` value, the newest one is kept. If
+/// `purge` is set, tombstones are dropped, which is only correct if there
+/// are no older runs left.
+fn merge<const D: usize, T: Size<D>, V>(runs: Vec<Run<D, T, V>>, purge: bool) -> Vec<Entry<D, T, V>> {
+    let len = runs.iter().map(|r| r.entries.len()).sum();
+    let mut iters = runs.into_iter().rev().map(|r| r.entries.into_iter().peekable()).collect::<Vec<_>>();
+    let mut entries = Vec::with_capacity(len);
+    while let Some(e) = next_newest(&mut iters) {
+        if !(purge && e.1.is_none()) {
+            entries.push(e)
+        }
+    }
+    entries
+}
+
+/// Take the entries with the smallest `Z` value from sorted iterators.
+///
+/// The iterators are ordered from newest to oldest and the newest entry is
+/// returned.
+fn next_newest<const D: usize, T, E, I>(iters: &mut [Peekable<I>]) -> Option<E>
+where
+    T: Size<D>,
+    E: GetZ<D, T>,
+    I: Iterator<Item = E>
+{
+    let z = iters.iter_mut().filter_map(|i| i.peek().map(|e| *e.z())).min()?;
+    let mut newest = None;
+    for i in iters {
+        if let Some(e) = i.next_if(|e| *e.z() == z) {
+            newest.get_or_insert(e);
+        }
+    }
+    newest
+}
+
+/// Sorted entries within a bounding box.
+enum Source<'a, const D: usize, T: Size<D>, V> {
+    Buffer(vec::IntoIter<&'a Entry<D, T, V>>),
+    Run(Zrange<'a, 'a, D, T, Entry<D, T, V>>)
+}
+
+impl<'a, const D: usize, T: Size<D>, V> Iterator for Source<'a, D, T, V> {
+    type Item = &'a Entry<D, T, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            | Source::Buffer(i) => i.next(),
+            | Source::Run(i)    => i.next()
+        }
+    }
+}
+
+/// Iterator over the entries of an [`LsmIndex`] within a bounding box.
+///
+/// Created by [`LsmIndex::range`].
+pub struct LsmRange<'a, const D: usize, T: Size<D>, V> {
+    /// The buffer and runs from newest to oldest.
+    sources: Vec<Peekable<Source<'a, D, T, V>>>
+}
+
+impl<'a, const D: usize, T: Size<D>, V> Iterator for LsmRange<'a, D, T, V> {
+    type Item = (&'a Z<D, T>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (z, v) = next_newest(&mut self.sources)?;
+            if let Some(v) = v {
+                return Some((z, v))
+            }
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FusedIterator for LsmRange<'_, D, T, V> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::LsmIndex;
+
+    #[test]
+    fn lsm_index() {
+        let mut index = LsmIndex::new().buffer_len(64).fanout(3);
+        let mut map = BTreeMap::new();
+        for i in 0 .. 20_000 {
+            let z = Z::from(rand::random::<[u8; 2]>());
+            if rand::random::<u8>() < 64 {
+                map.remove(&z);
+                index.remove(&z)
+            } else {
+                map.insert(z, i);
+                index.insert(z, i)
+            }
+            if i % 1000 == 0 {
+                for (z, v) in &map {
+                    assert_eq!(Some(v), index.get(z))
+                }
+            }
+        }
+        // The runs count the flushes like the digits of a number in base
+        // `fanout`, unless a merge removed all entries.
+        let (mut flushes, mut bound) = (20_000 / 64, 0);
+        while flushes > 0 {
+            bound += flushes % 3;
+            flushes /= 3
+        }
+        assert!(index.runs() <= bound);
+
+        let vec = map.iter().map(|(z, v)| (*z, *v)).collect::<Vec<_>>();
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).copied().collect::<Vec<_>>();
+            assert_eq!(expected, index.range(min, max).map(|(z, v)| (*z, *v)).collect::<Vec<_>>())
+        }
+
+        index.compact();
+        assert_eq!(1, index.runs());
+        assert_eq!(vec, index.range([0, 0], [255, 255]).map(|(z, v)| (*z, *v)).collect::<Vec<_>>())
+    }
+}
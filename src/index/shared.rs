@@ -0,0 +1,338 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
+use core::slice;
+use num_traits::ToPrimitive;
+use std::sync::{PoisonError, RwLock};
+use crate::{Bbox, Size, Z};
+use crate::search::Zrange;
+use super::{Duplicates, ZIndex};
+
+type Shards<const D: usize, T, V> = Vec<Arc<ZIndex<D, T, V>>>;
+
+/// A thread-safe map from `Z` values to values.
+///
+/// Entries are distributed over shards by the leading bits of their `Z`
+/// values, so that each shard covers a contiguous part of the curve. Readers
+/// take a [`Snapshot`] of all shards at once, which gives consistent results
+/// no matter what writers do in the meantime. Writers modify the shards in
+/// place, except for those still held by a snapshot, which are copied first.
+/// Writes are serialised and delay taking snapshots, so edits should be
+/// batched where possible (cf. [`SharedIndex::extend`]).
+pub struct SharedIndex<const D: usize, T: Size<D>, V> {
+    current: RwLock<Arc<Shards<D, T, V>>>,
+    bits: u32,
+    duplicates: Duplicates
+}
+
+impl<const D: usize, T: Size<D>, V: Clone> Default for SharedIndex<D, T, V> {
+    fn default() -> Self {
+        Self::with_duplicates(Duplicates::default())
+    }
+}
+
+impl<const D: usize, T: Size<D>, V: Clone> SharedIndex<D, T, V> {
+    /// The default number of leading `Z` value bits which select a shard.
+    const BITS: u32 = 4;
+
+    /// Create an empty index which replaces entries with equal `Z` values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty index with the given duplicates policy.
+    pub fn with_duplicates(duplicates: Duplicates) -> Self {
+        SharedIndex {
+            current: RwLock::new(Arc::new(Self::shards(Self::BITS, duplicates))),
+            bits: Self::BITS,
+            duplicates
+        }
+    }
+
+    /// Set the number of leading `Z` value bits which select a shard (default = 4).
+    ///
+    /// The index has `2^bits` shards. Existing entries are redistributed.
+    pub fn shard_bits(mut self, bits: u32) -> Self {
+        let bits = bits.min(16).min(width::<D, T>());
+        let old = self.snapshot();
+        self.bits = bits;
+        *self.current.get_mut().unwrap_or_else(PoisonError::into_inner) = Arc::new(Self::shards(bits, self.duplicates));
+        self.extend(old.shards.iter().flat_map(|s| s.as_slice().iter().cloned()));
+        self
+    }
+
+    fn shards(bits: u32, duplicates: Duplicates) -> Shards<D, T, V> {
+        (0 .. 1 << bits).map(|_| Arc::new(ZIndex::with_duplicates(duplicates))).collect()
+    }
+
+    /// Get a consistent view of all entries.
+    pub fn snapshot(&self) -> Snapshot<D, T, V> {
+        let shards = self.current.read().unwrap_or_else(PoisonError::into_inner).clone();
+        Snapshot { shards, bits: self.bits }
+    }
+
+    /// Modify the shards while holding the lock.
+    ///
+    /// The set of shards is only copied if a snapshot holds it, so that
+    /// [`Arc::make_mut`] only copies the shards also held by a snapshot. If
+    /// `f` panics, the modifications made so far remain, but every shard is
+    /// still a valid index. Lock poisoning can therefore be ignored.
+    fn write<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Shards<D, T, V>) -> R
+    {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        f(Arc::make_mut(&mut current))
+    }
+
+    /// Insert an entry.
+    ///
+    /// See [`ZIndex::insert`] for details.
+    pub fn insert(&self, z: Z<D, T>, value: V) -> Option<V> {
+        let i = shard(self.bits, &z);
+        self.write(|shards| Arc::make_mut(&mut shards[i]).insert(z, value))
+    }
+
+    /// Remove the first entry with the given `Z` value.
+    pub fn remove(&self, z: &Z<D, T>) -> Option<V> {
+        let i = shard(self.bits, z);
+        self.write(|shards| Arc::make_mut(&mut shards[i]).remove(z))
+    }
+
+    /// Move the first entry with the `Z` value `from` to `to`.
+    ///
+    /// Returns `Err(*from)` if there is no entry at `from`. See
+    /// [`ZIndex::update`] for details.
+    pub fn update(&self, from: &Z<D, T>, to: Z<D, T>) -> Result<Option<V>, Z<D, T>> {
+        let (i, j) = (shard(self.bits, from), shard(self.bits, &to));
+        self.write(|shards| {
+            if !shards[i].contains(from) {
+                return Err(*from)
+            }
+            let value = Arc::make_mut(&mut shards[i]).remove(from).ok_or(*from)?;
+            Ok(Arc::make_mut(&mut shards[j]).insert(to, value))
+        })
+    }
+
+    /// Add entries in any order.
+    ///
+    /// All entries become visible at once and every shard is copied at most once.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (Z<D, T>, V)>
+    {
+        let mut parts = (0 .. 1usize << self.bits).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (z, v) in iter {
+            parts[shard(self.bits, &z)].push((z, v))
+        }
+        self.write(|shards| {
+            for (s, p) in shards.iter_mut().zip(parts) {
+                if !p.is_empty() {
+                    Arc::make_mut(s).extend(p)
+                }
+            }
+        })
+    }
+
+    /// Retain only the entries within the bounding box for which the predicate holds.
+    ///
+    /// See [`ZIndex::retain_in`] for details.
+    pub fn retain_in<F>(&self, bbox: &Bbox<D, T>, mut f: F)
+    where
+        F: FnMut(&Z<D, T>, &mut V) -> bool
+    {
+        let r = shards_of(self.bits, bbox);
+        self.write(|shards| {
+            for s in &mut shards[r] {
+                Arc::make_mut(s).retain_in(bbox, &mut f)
+            }
+        })
+    }
+
+    /// Remove all entries within the bounding box.
+    ///
+    /// The removed entries are returned in ascending `Z` order.
+    pub fn drain_in(&self, bbox: &Bbox<D, T>) -> Vec<(Z<D, T>, V)> {
+        let r = shards_of(self.bits, bbox);
+        self.write(|shards| {
+            let mut drained = Vec::new();
+            for s in &mut shards[r] {
+                if s.range(*bbox.min_parts(), *bbox.max_parts()).next().is_some() {
+                    drained.extend(Arc::make_mut(s).drain_in(bbox))
+                }
+            }
+            drained
+        })
+    }
+}
+
+impl<const D: usize, T: Size<D>, V: Clone> FromIterator<(Z<D, T>, V)> for SharedIndex<D, T, V> {
+    fn from_iter<I: IntoIterator<Item = (Z<D, T>, V)>>(iter: I) -> Self {
+        let index = SharedIndex::new();
+        index.extend(iter);
+        index
+    }
+}
+
+/// Get the number of bits of a `Z` value.
+fn width<const D: usize, T: Size<D>>() -> u32 {
+    D as u32 * T::zero().count_zeros()
+}
+
+/// Get the shard of a `Z` value from its leading bits.
+fn shard<const D: usize, T: Size<D>>(bits: u32, z: &Z<D, T>) -> usize {
+    if bits == 0 {
+        return 0
+    }
+    let shift = (width::<D, T>() - bits) as usize;
+    (z.point >> shift).to_usize().expect("shard index fits into usize")
+}
+
+/// Get the shards which may contain entries within the bounding box.
+fn shards_of<const D: usize, T: Size<D>>(bits: u32, bbox: &Bbox<D, T>) -> RangeInclusive<usize> {
+    shard(bits, &bbox.min()) ..= shard(bits, &bbox.max())
+}
+
+/// A consistent view of the entries of a [`SharedIndex`].
+///
+/// Created by [`SharedIndex::snapshot`]. A snapshot is not affected by
+/// later modifications of the index.
+#[derive(Clone)]
+pub struct Snapshot<const D: usize, T: Size<D>, V> {
+    shards: Arc<Shards<D, T, V>>,
+    bits: u32
+}
+
+impl<const D: usize, T: Size<D>, V> Snapshot<D, T, V> {
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.len()).sum()
+    }
+
+    /// Check if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Get the value of the first entry with the given `Z` value.
+    pub fn get(&self, z: &Z<D, T>) -> Option<&V> {
+        self.shards[shard(self.bits, z)].get(z)
+    }
+
+    /// Iterate over the entries within the bounding box.
+    ///
+    /// The entries are returned in ascending `Z` order.
+    pub fn range(&self, min: [T; D], max: [T; D]) -> SnapshotRange<'_, D, T, V> {
+        let r = shards_of(self.bits, &Bbox::new(min.into(), max.into()));
+        SnapshotRange { shards: self.shards[r].iter(), current: None, min, max }
+    }
+}
+
+/// Iterator over the entries of a [`Snapshot`] within a bounding box.
+///
+/// Created by [`Snapshot::range`].
+pub struct SnapshotRange<'a, const D: usize, T: Size<D>, V> {
+    shards: slice::Iter<'a, Arc<ZIndex<D, T, V>>>,
+    current: Option<Zrange<'a, 'a, D, T, (Z<D, T>, V)>>,
+    min: [T; D],
+    max: [T; D]
+}
+
+impl<'a, const D: usize, T: Size<D>, V> Iterator for SnapshotRange<'a, D, T, V> {
+    type Item = &'a (Z<D, T>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(e)
+            }
+            self.current = Some(self.shards.next()?.range(self.min, self.max))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FusedIterator for SnapshotRange<'_, D, T, V> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+    use crate::{search, Bbox, Z};
+    use super::SharedIndex;
+
+    #[test]
+    fn shared_index() {
+        let entries = (0 .. 5000).map(|i| (Z::from(rand::random::<[u8; 2]>()), i)).collect::<Vec<_>>();
+        let map = entries.iter().copied().collect::<BTreeMap<_, _>>();
+        let index = entries.iter().copied().collect::<SharedIndex<_, _, _>>();
+        let snapshot = index.snapshot();
+        for bits in [0, 1, 6, 16] {
+            let index = SharedIndex::new().shard_bits(bits);
+            index.extend(entries.iter().copied());
+            assert_eq!(map.len(), index.snapshot().len())
+        }
+
+        let vec = map.into_iter().collect::<Vec<_>>();
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, snapshot.range(min, max).collect::<Vec<_>>());
+            index.drain_in(&Bbox::new(Z::from(min), Z::from(max)));
+            assert_eq!(0, index.snapshot().range(min, max).count())
+        }
+        assert_eq!(vec.len(), snapshot.len());
+
+        let (from, to) = (Z::<2, u8>::from([0, 0]), Z::from([255, 255]));
+        let index = SharedIndex::new();
+        index.insert(from, 1);
+        assert_eq!(Ok(None), index.update(&from, to));
+        assert_eq!(Err(from), index.update(&from, to));
+        assert_eq!(Some(&1), index.snapshot().get(&to))
+    }
+
+    #[test]
+    fn single_inserts() {
+        let index = SharedIndex::<2, u8, u32>::new().shard_bits(0);
+        let shard = || Arc::as_ptr(&index.current.read().unwrap()[0]);
+        let first = shard();
+        let mut map = BTreeMap::new();
+        for i in 0 .. 10_000 {
+            let z = Z::from(rand::random::<[u8; 2]>());
+            assert_eq!(map.insert(z, i), index.insert(z, i));
+            // Without snapshots, the shard is modified instead of copied.
+            assert_eq!(first, shard())
+        }
+        let snapshot = index.snapshot();
+        index.insert(Z::from([0, 0]), 0);
+        assert_ne!(first, shard());
+        assert_eq!(map.len(), snapshot.len());
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), snapshot.range([0, 0], [255, 255]).copied().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn concurrent() {
+        let index = Arc::new(SharedIndex::<2, u8, u32>::new());
+        let writers = (0 .. 4u32).map(|t| {
+            let index = index.clone();
+            thread::spawn(move || {
+                for i in 0 .. 256 {
+                    // Every batch inserts one entry into each column.
+                    index.extend((0 .. 4).map(|x| (Z::from([x * 64 + t as u8, i as u8]), i)))
+                }
+            })
+        }).collect::<Vec<_>>();
+        for _ in 0 .. 100 {
+            let snapshot = index.snapshot();
+            let n = snapshot.range([0, 0], [255, 255]).count();
+            assert_eq!(0, n % 4)
+        }
+        for w in writers {
+            w.join().unwrap()
+        }
+        assert_eq!(4 * 4 * 256, index.snapshot().len())
+    }
+}
@@ -6,15 +6,38 @@
 //! [1]: https://en.wikipedia.org/wiki/Z-order_curve
 //! [2]: http://www.vision-tools.com/h-tropf/multidimensionalrangequery.pdf
 
+#![no_std]
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc};
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+
 mod z;
 mod size;
+mod sorted;
 
 pub mod search;
+#[cfg(feature = "alloc")]
+pub mod avl;
+#[cfg(feature = "alloc")]
+pub mod index;
+#[cfg(feature = "alloc")]
+pub mod sort;
 
-pub use z::{Bbox, Z};
+pub use z::{Bbox, Periodic, Z};
 pub use size::Size;
+pub use sorted::{debug_assert_sorted, NotSorted, ZSorted};
+#[cfg(feature = "alloc")]
+pub use sorted::ZSortedVec;
 
 /// A type that has a [`Z`] value.
 pub trait GetZ<const D: usize, T: Size<D>> {
@@ -26,3 +49,42 @@ impl<const D: usize, T: Size<D>> GetZ<D, T> for Z<D, T> {
         self
     }
 }
+
+impl<const D: usize, T: Size<D>, V> GetZ<D, T> for (Z<D, T>, V) {
+    fn z(&self) -> &Z<D, T> {
+        &self.0
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> GetZ<D, T> for &A {
+    fn z(&self) -> &Z<D, T> {
+        (**self).z()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> GetZ<D, T> for &mut A {
+    fn z(&self) -> &Z<D, T> {
+        (**self).z()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> GetZ<D, T> for Box<A> {
+    fn z(&self) -> &Z<D, T> {
+        (**self).z()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> GetZ<D, T> for Rc<A> {
+    fn z(&self) -> &Z<D, T> {
+        (**self).z()
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> GetZ<D, T> for Arc<A> {
+    fn z(&self) -> &Z<D, T> {
+        (**self).z()
+    }
+}
@@ -1,121 +1,788 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+use num_traits::ToPrimitive;
 use crate::{Bbox, Size, GetZ, Z};
 
-/// Iterate over a bounding box of `Z` values.
+#[cfg(feature = "alloc")]
+mod zrange;
+#[cfg(feature = "alloc")]
+mod eytzinger;
+#[cfg(feature = "alloc")]
+mod btree;
+#[cfg(feature = "alloc")]
+mod tree;
+#[cfg(feature = "alloc")]
+mod merge;
+#[cfg(feature = "rayon")]
+mod par;
+mod fixed;
+mod scan;
+mod seek;
+
+#[cfg(feature = "alloc")]
+pub use zrange::{range, range_by_key, range_mut, range_mut_by_key, range_periodic, range_with, resume, resume_by_key};
+#[cfg(feature = "alloc")]
+pub use zrange::{Indices, Query, Runs, Searcher, Slices, Zrange, ZrangeMut, ZrangeOwned, ZrangePeriodic, Zsearch};
+#[cfg(feature = "alloc")]
+pub use eytzinger::{Eytzinger, EytzingerRange};
+#[cfg(feature = "alloc")]
+pub use btree::{range_map, range_set, MapRange, SetRange};
+#[cfg(feature = "alloc")]
+pub use tree::{range_tree, TreeNode, TreeRange};
+#[cfg(feature = "alloc")]
+pub use merge::{range_merged, range_merged_by_key, Zmerge};
+#[cfg(feature = "rayon")]
+pub use par::{par_range, par_range_by_key, ParZrange};
+pub use fixed::{capacity, FixedSearcher, ZrangeFixed};
+pub use scan::{scan, scan_by_key, Strategy, Zscan};
+pub use seek::{seek_range, SeekRange, SliceCursor, ZCursor};
+#[cfg(feature = "alloc")]
+pub use seek::{MapCursor, SetCursor};
+
+/// Update the statistics of a traversal, if enabled.
+macro_rules! stat {
+    ($t:expr, $($f:tt)*) => {
+        #[cfg(feature = "stats")]
+        { $t.stats.$($f)* }
+    }
+}
+
+/// A way to get the `Z` value of an item.
 ///
-/// Out of a sequence of values that are sorted by their `Z` value, get all
-/// elements within a bounding box.
-pub fn range<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D]) -> Zrange<D, T, A>
+/// This is implemented for [`ByZ`], which uses the item's [`GetZ`] impl, and
+/// for all functions from `&A` to `Z<D, T>`.
+pub trait Key<const D: usize, T: Size<D>, A: ?Sized> {
+    /// Get the `Z` value of the given item.
+    fn key(&self, item: &A) -> Z<D, T>;
+}
+
+/// Gets the `Z` value of an item from its [`GetZ`] impl.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ByZ;
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T> + ?Sized> Key<D, T, A> for ByZ {
+    fn key(&self, item: &A) -> Z<D, T> {
+        *item.z()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: ?Sized, F> Key<D, T, A> for F
 where
-    T: Size<D>,
-    A: GetZ<D, T>
+    F: Fn(&A) -> Z<D, T>
 {
-    let bbox = Bbox::new(min.into(), max.into());
-    Zrange {
-        stack: vec![Frame { items, min: bbox.min(), max: bbox.max() }],
-        bbox,
-        threshold: 10
+    fn key(&self, item: &A) -> Z<D, T> {
+        self(item)
     }
 }
 
-/// Iterator over `Z` values.
-pub struct Zrange<'a, const D: usize, T: Size<D>, A> {
-    stack: Vec<Frame<'a, D, T, A>>,
+/// The state of a range search, independent of the items searched.
+///
+/// Items are referred to by their index. The frames are kept in ascending
+/// order of their positions, so that matches can be produced in ascending
+/// `Z` order from the front and in descending order from the back.
+#[derive(Clone)]
+struct Traversal<const D: usize, T: Size<D>, S> {
+    frames: S,
     bbox: Bbox<D, T>,
-    threshold: usize
+    threshold: Threshold,
+    /// Choose split points by interpolation instead of bisection.
+    interpolate: bool,
+    /// The index of the last item returned from the front.
+    last: Option<usize>,
+    /// The `Z` value of the last item and the number of items with this
+    /// `Z` value returned before the search was resumed.
+    resumed: Option<(Z<D, T>, usize)>,
+    #[cfg(feature = "stats")]
+    stats: Stats
+}
+
+/// Counters of a range search.
+///
+/// Requires the `stats` feature. The counters are reset whenever a search
+/// is (re-)started.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of frames pushed onto the stack of pending work.
+    pub frames_pushed: usize,
+    /// The number of items whose `Z` value has been compared.
+    pub items_compared: usize,
+    /// The number of items within the bounding box.
+    pub items_matched: usize,
+    /// The number of `litmax` calculations.
+    pub litmax: usize,
+    /// The number of `bigmin` calculations.
+    pub bigmin: usize,
+    /// The maximum number of pending frames at any time.
+    pub max_depth: usize,
+    /// The number of ranges split at their midpoint.
+    pub bisections: usize,
+    /// The number of ranges split at an interpolated point.
+    pub interpolations: usize
 }
 
-impl<'a, const D: usize, T: Size<D>, A: GetZ<D, T>> Zrange<'a, D, T, A> {
-    /// Set litmax/bigmin optimization threshold (default = 10).
+#[cfg(feature = "stats")]
+impl Stats {
+    /// Combine the counters of two searches.
+    #[cfg(feature = "alloc")]
+    fn merge(&mut self, other: &Stats) {
+        self.frames_pushed  += other.frames_pushed;
+        self.items_compared += other.items_compared;
+        self.items_matched  += other.items_matched;
+        self.litmax         += other.litmax;
+        self.bigmin         += other.bigmin;
+        self.max_depth       = self.max_depth.max(other.max_depth);
+        self.bisections     += other.bisections;
+        self.interpolations += other.interpolations
+    }
+}
+
+/// When to calculate litmax and bigmin during a range search.
+#[derive(Debug, Clone, Copy)]
+struct Threshold {
+    /// Calculate only if the number of remaining elements is larger.
+    value: usize,
+    /// Adjust `value` depending on whether calculations pay off.
+    adaptive: bool
+}
+
+impl Threshold {
+    /// The largest value an adaptive threshold grows to.
+    const MAX: usize = 64;
+
+    /// The number of elements a calculation needs to skip to pay off.
+    const GAIN: usize = 4;
+
+    const fn fixed(value: usize) -> Self {
+        Threshold { value, adaptive: false }
+    }
+
+    const fn adaptive() -> Self {
+        Threshold { value: 10, adaptive: true }
+    }
+
+    /// Adjust an adaptive threshold after a litmax or bigmin calculation.
     ///
-    /// During range search, litmax and bigmin will only be calculated and used
-    /// if the remaining number of elements is larger than the threshold value.
-    pub fn optimize_if_gt(mut self, t: usize) -> Self {
-        self.threshold = t;
-        self
+    /// If the calculation allowed skipping elements, the threshold is halved,
+    /// otherwise it is incremented.
+    fn update(&mut self, pruned: bool) {
+        if pruned {
+            self.value /= 2
+        } else {
+            self.value = (self.value + 1).min(Self::MAX)
+        }
+    }
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Threshold::fixed(10)
     }
 }
 
-struct Frame<'a, const D: usize, T: Size<D>, A> {
-    items: &'a [A],
+/// The items `start .. end` whose `Z` values are within `min ..= max`.
+#[derive(Clone, Copy)]
+struct Frame<const D: usize, T: Size<D>> {
+    start: usize,
+    end: usize,
     min: Z<D, T>,
-    max: Z<D, T>
+    max: Z<D, T>,
+    /// Whether the item right before `start` is within the bounding box and
+    /// still to be returned. Its `Z` value is `min`.
+    pending: bool,
+    /// A lower bound of the `Z` values of the items taken from the data,
+    /// i.e. the `Z` value of the item at or before `start`, if known.
+    lo: Option<Z<D, T>>,
+    /// An upper bound of the `Z` values of the items taken from the data,
+    /// i.e. the `Z` value of the item before or at `end`, if known.
+    hi: Option<Z<D, T>>,
+    /// Whether interpolation has failed for an enclosing frame.
+    skewed: bool,
+    /// Whether all items of the frame are known to be within the bounding
+    /// box.
+    inside: bool
 }
 
-impl<'a, const D: usize, T: Size<D>, A> Iterator for Zrange<'a, D, T, A>
-where
-    A: GetZ<D, T>
-{
-    type Item = &'a A;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        while let Some(frame) = self.stack.pop() {
-            match frame.items.split_at(frame.items.len() / 2) {
-            | (lower, [mid, upper @ ..]) => {
-                let midz = *mid.z();
-                if midz < frame.min {
-                    self.stack.push(Frame { items: upper, min: frame.min, max: frame.max })
-                } else if midz > frame.max {
-                    self.stack.push(Frame { items: lower, min: frame.min, max: frame.max })
+impl<const D: usize, T: Size<D>> Frame<D, T> {
+    fn new(start: usize, end: usize, min: Z<D, T>, max: Z<D, T>) -> Self {
+        Frame { start, end, min, max, pending: false, lo: None, hi: None, skewed: false, inside: false }
+    }
+
+    /// Keep a frame only if it contains items or a pending match.
+    fn keep(self) -> Option<Self> {
+        (self.start < self.end || self.pending).then_some(self)
+    }
+}
+
+/// Storage of traversal frames, used as a double-ended queue.
+trait Frames<const D: usize, T: Size<D>>: Default {
+    fn push_front(&mut self, f: Frame<D, T>);
+    fn push_back(&mut self, f: Frame<D, T>);
+    fn pop_front(&mut self) -> Option<Frame<D, T>>;
+    fn pop_back(&mut self) -> Option<Frame<D, T>>;
+    fn front_mut(&mut self) -> Option<&mut Frame<D, T>>;
+    fn clear(&mut self);
+    #[cfg(feature = "stats")]
+    fn len(&self) -> usize;
+}
+
+/// A double-ended queue of frames made of two stacks.
+///
+/// Frames pushed to the front are kept on one stack and frames pushed to
+/// the back on the other one, so that iterating from one end only involves
+/// a single stack. Only if one stack runs empty, frames are taken from the
+/// bottom of the other one.
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+struct Deque<const D: usize, T: Size<D>> {
+    /// The frames at the front, the first one on top.
+    front: Vec<Frame<D, T>>,
+    /// The frames at the back, the last one on top.
+    back: Vec<Frame<D, T>>
+}
+
+#[cfg(any(feature = "stats", feature = "rayon"))]
+impl<const D: usize, T: Size<D>> Deque<D, T> {
+    fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>> Default for Deque<D, T> {
+    fn default() -> Self {
+        Deque { front: Vec::new(), back: Vec::new() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>> Frames<D, T> for Deque<D, T> {
+    fn push_front(&mut self, f: Frame<D, T>) {
+        self.front.push(f)
+    }
+
+    fn push_back(&mut self, f: Frame<D, T>) {
+        self.back.push(f)
+    }
+
+    fn pop_front(&mut self) -> Option<Frame<D, T>> {
+        match self.front.pop() {
+            | None if !self.back.is_empty() => Some(self.back.remove(0)),
+            | f                             => f
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<Frame<D, T>> {
+        match self.back.pop() {
+            | None if !self.front.is_empty() => Some(self.front.remove(0)),
+            | f                              => f
+        }
+    }
+
+    fn front_mut(&mut self) -> Option<&mut Frame<D, T>> {
+        match self.front.last_mut() {
+            | None => self.back.first_mut(),
+            | f    => f
+        }
+    }
+
+    fn clear(&mut self) {
+        self.front.clear();
+        self.back.clear()
+    }
+
+    #[cfg(feature = "stats")]
+    fn len(&self) -> usize {
+        Deque::len(self)
+    }
+}
+
+impl<const D: usize, T: Size<D>, S: Frames<D, T>> Traversal<D, T, S> {
+    fn new(len: usize, bbox: Bbox<D, T>, threshold: Threshold) -> Self {
+        let mut this = Self {
+            frames: S::default(),
+            bbox,
+            threshold,
+            interpolate: false,
+            last: None,
+            resumed: None,
+            #[cfg(feature = "stats")]
+            stats: Stats::default()
+        };
+        this.reset(len);
+        this
+    }
+
+    /// Continue a search at the given cursor position with its settings,
+    /// keeping the allocated memory.
+    fn resume<A, K: Key<D, T, A>>(&mut self, items: &[A], cursor: &Cursor<D, T>, key: &K) {
+        self.bbox = cursor.bbox();
+        self.threshold = Threshold { value: cursor.threshold, adaptive: cursor.adaptive };
+        self.interpolate = cursor.interpolate;
+        self.reset(items.len());
+        if let Some(last) = cursor.last {
+            let start = items.partition_point(|a| key.key(a) < last).saturating_add(cursor.skip);
+            self.frames.clear();
+            #[cfg(feature = "stats")]
+            { self.stats = Stats::default() }
+            if let Some(f) = Frame::new(start.min(items.len()), items.len(), last, self.bbox.max()).keep() {
+                self.frames.push_back(f);
+                self.pushed(1)
+            }
+            self.resumed = Some((last, cursor.skip))
+        }
+    }
+
+    /// Restart the search over `len` items, keeping the allocated memory.
+    fn reset(&mut self, len: usize) {
+        self.frames.clear();
+        #[cfg(feature = "stats")]
+        { self.stats = Stats::default() }
+        if let Some(f) = Frame::new(0, len, self.bbox.min(), self.bbox.max()).keep() {
+            self.frames.push_back(f);
+            self.pushed(1)
+        }
+        self.last = None;
+        self.resumed = None
+    }
+
+    /// Record that `_n` frames have been pushed.
+    #[inline(always)]
+    fn pushed(&mut self, _n: usize) {
+        stat!(self, frames_pushed += _n);
+        stat!(self, max_depth = self.stats.max_depth.max(self.frames.len()))
+    }
+
+    /// Get the cursor after the last item returned from the front.
+    fn cursor<F>(&self, z: F) -> Cursor<D, T>
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        let (last, skip) = match (self.last, self.resumed) {
+            | (Some(i), _) => {
+                // All items with the same `Z` value up to `i` have been returned.
+                let zi = z(i);
+                let (mut lo, mut hi) = (0, i);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if z(mid) < zi { lo = mid + 1 } else { hi = mid }
+                }
+                (Some(zi), i + 1 - lo)
+            }
+            | (None, Some((z, n))) => (Some(z), n),
+            | (None, None)         => (None, 0)
+        };
+        self.cursor_at(last, skip)
+    }
+
+    /// Get a cursor after the `skip`-th item with the `Z` value `last`.
+    fn cursor_at(&self, last: Option<Z<D, T>>, skip: usize) -> Cursor<D, T> {
+        Cursor {
+            min: self.bbox.min(),
+            max: self.bbox.max(),
+            last,
+            skip,
+            threshold: self.threshold.value,
+            adaptive: self.threshold.adaptive,
+            interpolate: self.interpolate
+        }
+    }
+
+    /// Get the index of the next matching item in ascending `Z` order.
+    fn next<F>(&mut self, z: F) -> Option<usize>
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        // Compile the search without interpolation and adaptation unless
+        // they are used, which makes a notable difference.
+        if self.interpolate || self.threshold.adaptive {
+            self.next_tuned::<true, F>(z)
+        } else {
+            self.next_tuned::<false, F>(z)
+        }
+    }
+
+    fn next_tuned<const TUNED: bool, F>(&mut self, z: F) -> Option<usize>
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        loop {
+            let f = self.frames.front_mut()?;
+            if f.pending {
+                f.pending = false;
+                let i = f.start - 1;
+                if f.start == f.end {
+                    self.frames.pop_front();
+                }
+                return Some(self.matched(i))
+            }
+            if f.inside {
+                let i = f.start;
+                f.start += 1;
+                if f.start == f.end {
+                    self.frames.pop_front();
+                }
+                return Some(self.matched(i))
+            }
+            // Descend into the lower part and push the upper part, unless
+            // it can be skipped or all of its items are within the bounding
+            // box.
+            let mut f = self.frames.pop_front()?;
+            if f.end - f.start > 1 && self.inside(&f, &z) {
+                f.inside = true;
+                self.frames.push_front(f);
+                continue
+            }
+            while f.start < f.end {
+                let (mid, midz) = self.split_point::<TUNED, F>(&mut f, &z);
+                if midz < f.min {
+                    f.start = mid + 1;
+                    f.lo = Some(midz)
+                } else if midz > f.max {
+                    f.end = mid;
+                    f.hi = Some(midz)
                 } else if self.bbox.contains(&midz) {
-                    self.stack.push(Frame { items: upper, min: midz, max: frame.max });
-                    self.stack.push(Frame { items: lower, min: frame.min, max: midz });
-                    return Some(mid)
-                } else {
-                    if upper.len() > self.threshold {
-                        let bigmin = self.bbox.bigmin(&midz);
-                        self.stack.push(Frame { items: upper, min: bigmin, max: frame.max })
-                    } else {
-                        self.stack.push(Frame { items: upper, min: midz, max: frame.max })
+                    if mid == f.start {
+                        if mid + 1 < f.end {
+                            self.frames.push_front(Frame { start: mid + 1, min: midz, lo: Some(midz), ..f });
+                            self.pushed(1)
+                        }
+                        return Some(self.matched(mid))
                     }
-                    if lower.len() > self.threshold {
-                        let litmax = self.bbox.litmax(&midz);
-                        self.stack.push(Frame { items: lower, min: frame.min, max: litmax })
-                    } else {
-                        self.stack.push(Frame { items: lower, min: frame.min, max: midz })
+                    self.frames.push_front(Frame { start: mid + 1, min: midz, pending: true, lo: Some(midz), ..f });
+                    self.pushed(1);
+                    f.end = mid;
+                    f.max = midz;
+                    f.hi = Some(midz)
+                } else {
+                    let (max, min) = self.bounds::<TUNED, F>(&f, mid, midz, &z);
+                    if mid + 1 < f.end {
+                        self.frames.push_front(Frame { start: mid + 1, min, lo: Some(midz), ..f });
+                        self.pushed(1)
                     }
+                    f.end = mid;
+                    f.max = max;
+                    f.hi = Some(midz)
                 }
             }
-            | ([], []) => continue,
-            | _        => unreachable!()
+        }
+    }
+
+    /// Check if all items of a frame are within the bounding box, which is
+    /// the case if they are within `min ..= max` and these are within a block
+    /// of the bounding box.
+    #[inline(always)]
+    fn inside<F>(&mut self, f: &Frame<D, T>, z: &F) -> bool
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        self.bbox.covers(&f.min, &f.max) && {
+            stat!(self, items_compared += 2);
+            z(f.start) >= f.min && z(f.end - 1) <= f.max
+        }
+    }
+
+    /// Record that the item `i` is returned from the front.
+    #[inline(always)]
+    fn matched(&mut self, i: usize) -> usize {
+        stat!(self, items_matched += 1);
+        self.last = Some(i);
+        i
+    }
+
+    /// Get the index of the next matching item in descending `Z` order.
+    fn next_back<F>(&mut self, z: F) -> Option<usize>
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        while let Some(mut f) = self.frames.pop_back() {
+            // Continue with the upper part, or with the lower part if there
+            // is no upper part, instead of pushing it.
+            loop {
+                if f.start == f.end {
+                    stat!(self, items_matched += 1);
+                    return Some(f.start - 1)
+                }
+                if f.inside {
+                    f.end -= 1;
+                    let i = f.end;
+                    if let Some(f) = f.keep() {
+                        self.frames.push_back(f)
+                    }
+                    stat!(self, items_matched += 1);
+                    return Some(i)
+                }
+                match self.split(f, &z) {
+                    | [Some(l), Some(u)] => {
+                        self.frames.push_back(l);
+                        self.pushed(1);
+                        f = u
+                    }
+                    | [None, Some(f2)] | [Some(f2), None] => f = f2,
+                    | [None, None] => break
+                }
             }
         }
         None
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::Z;
+    /// Split a frame at its midpoint or an interpolated point.
+    ///
+    /// Returns the lower and the upper frame. A pending match stays with the
+    /// lower frame and the split item becomes the pending match of the upper
+    /// frame if it is within the bounding box.
+    fn split<F>(&mut self, mut f: Frame<D, T>, z: F) -> [Option<Frame<D, T>>; 2]
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        let (mid, midz) = self.split_point::<true, _>(&mut f, &z);
+        let lower = Frame { end: mid, hi: Some(midz), ..f };
+        let upper = Frame { start: mid + 1, pending: false, lo: Some(midz), ..f };
+        if midz < f.min {
+            [Frame { end: f.start, ..lower }.keep(), upper.keep()]
+        } else if midz > f.max {
+            [lower.keep(), None]
+        } else if self.bbox.contains(&midz) {
+            [Frame { max: midz, ..lower }.keep(), Some(Frame { min: midz, pending: true, ..upper })]
+        } else {
+            let (max, min) = self.bounds::<true, _>(&f, mid, midz, &z);
+            [Frame { max, ..lower }.keep(), Frame { min, ..upper }.keep()]
+        }
+    }
 
-    #[test]
-    fn area() {
-        let mut vec = Vec::new();
-        for x in 0 .. 9u8 {
-            for y in 0 .. 17u8 {
-                vec.push(Z::from([y, x]));
+    /// Choose the item to split a non-empty frame at and get its `Z` value.
+    #[inline(always)]
+    fn split_point<const TUNED: bool, F>(&mut self, f: &mut Frame<D, T>, z: &F) -> (usize, Z<D, T>)
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        if TUNED && self.interpolate && !f.skewed {
+            // Work on a copy, so that the frame itself can stay in registers.
+            let mut g = *f;
+            let p = self.interpolation_point(&mut g, z);
+            *f = g;
+            if let Some(p) = p {
+                return p
             }
         }
-        vec.sort_unstable();
-
-        let mut res = (0 .. 129).map(Z::new).collect::<Vec<_>>();
-        res.extend([
-            129, 132, 133, 144, 145, 148, 149, 192, 193, 196, 197, 208,
-            209, 212, 213, 256, 258, 264, 266, 288, 290, 296, 298, 384
-        ].map(Z::new));
+        let mid = f.start + (f.end - f.start) / 2;
+        stat!(self, bisections += 1);
+        stat!(self, items_compared += 1);
+        (mid, z(mid))
+    }
 
-        assert_eq!(&vec, &res);
+    /// Get the bounds of the `Z` values within the bounding box below and
+    /// above `midz`, which is outside of it, for the items of the frame below
+    /// and above `mid`.
+    ///
+    /// The bounds are only calculated for enough items, otherwise `midz` is
+    /// returned.
+    #[inline(always)]
+    fn bounds<const TUNED: bool, F>(&mut self, f: &Frame<D, T>, mid: usize, midz: Z<D, T>, z: &F) -> (Z<D, T>, Z<D, T>)
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        let min = if f.end - mid - 1 > self.threshold.value {
+            stat!(self, bigmin += 1);
+            let bigmin = self.bbox.bigmin(&midz);
+            if TUNED && self.threshold.adaptive {
+                let pruned = f.end - mid - 1 > Threshold::GAIN && {
+                    stat!(self, items_compared += 1);
+                    z(mid + Threshold::GAIN) < bigmin
+                };
+                self.threshold.update(pruned)
+            }
+            bigmin
+        } else {
+            midz
+        };
+        let max = if mid - f.start > self.threshold.value {
+            stat!(self, litmax += 1);
+            let litmax = self.bbox.litmax(&midz);
+            if TUNED && self.threshold.adaptive {
+                let pruned = mid - f.start > Threshold::GAIN && {
+                    stat!(self, items_compared += 1);
+                    z(mid - Threshold::GAIN) > litmax
+                };
+                self.threshold.update(pruned)
+            }
+            litmax
+        } else {
+            midz
+        };
+        (max, min)
+    }
 
-        let min = [5,  3];
-        let max = [10, 5];
+    /// Choose a split point by interpolation.
+    ///
+    /// The `Z` values of the items of the frame are bounded by those of the
+    /// items right next to it, which are known from previous splits, or else
+    /// by those of its first and last item. If items at either end of the
+    /// frame are outside of `min ..= max`, their number is estimated by linear
+    /// interpolation between these bounds and the larger group is probed at
+    /// its estimated border. If the probed item is indeed outside, it is
+    /// returned together with its `Z` value, so that the whole group is cut
+    /// off by splitting there. If it is not, i.e. the data is skewed, or if the
+    /// frame is small, `None` is returned and the frame is bisected instead.
+    fn interpolation_point<F>(&mut self, f: &mut Frame<D, T>, z: F) -> Option<(usize, Z<D, T>)>
+    where
+        F: Fn(usize) -> Z<D, T>
+    {
+        /// The minimum number of items to interpolate.
+        const MIN_LEN: usize = 1024;
 
-        let mut res = Vec::new();
-        for z in super::range(vec.as_slice(), min, max) {
-            res.push(z.point);
+        let len = f.end - f.start;
+        if len < MIN_LEN {
+            return None
+        }
+        let first = *f.lo.get_or_insert_with(|| {
+            stat!(self, items_compared += 1);
+            z(f.start)
+        });
+        let last = *f.hi.get_or_insert_with(|| {
+            stat!(self, items_compared += 1);
+            z(f.end - 1)
+        });
+        // If the frame is outside of `min ..= max`, split it off completely.
+        if first > f.max || last < f.min {
+            let i = if first > f.max { f.start } else { f.end - 1 };
+            stat!(self, items_compared += 1);
+            stat!(self, interpolations += 1);
+            return Some((i, z(i)))
         }
-        res.sort_unstable();
-        assert_eq! {
-            &[27, 30, 31, 49, 51, 52, 53, 54, 55, 74, 75, 78, 96, 97, 98, 99, 100, 102],
-            res.as_slice()
+        if first == last {
+            return None
+        }
+        let lo = first.point.to_f64().unwrap_or(f64::MAX);
+        let hi = last.point.to_f64().unwrap_or(f64::MAX);
+        // The estimated number of items less than `b`.
+        let below = |b: Z<D, T>| {
+            let b = b.point.to_f64().unwrap_or(f64::MAX).clamp(lo, hi);
+            ((b - lo) / (hi - lo) * len as f64) as usize
+        };
+        // Aim a little inside of the groups to make up for estimation errors.
+        let margin = len.isqrt();
+        let lower = if first < f.min { below(f.min).saturating_sub(margin) } else { 0 };
+        let upper = if last > f.max { (len - below(f.max).max(1)).saturating_sub(margin) } else { 0 };
+        let probe = if lower >= upper && lower > margin {
+            // The last item estimated to be less than `min`.
+            let i = f.start + lower.min(len - 1) - 1;
+            let zi = z(i);
+            (zi < f.min).then_some((i, zi))
+        } else if upper > margin {
+            // The first item estimated to be greater than `max`.
+            let i = f.end - upper.min(len - 1);
+            let zi = z(i);
+            (zi > f.max).then_some((i, zi))
+        } else {
+            return None
+        };
+        stat!(self, items_compared += 1);
+        if probe.is_some() {
+            stat!(self, interpolations += 1)
+        } else {
+            f.skewed = true
+        }
+        probe
+    }
+}
+
+/// The position of a range search.
+///
+/// A cursor contains the bounding box, the settings and the last returned `Z`
+/// value of a range search. It can be used to resume the search later, e.g.
+/// to page through large results.
+#[cfg_attr(feature = "alloc", doc = "
+See [`resume`] for details.")]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize   = "<T as Size<D>>::Output: serde::Serialize",
+    deserialize = "<T as Size<D>>::Output: serde::Deserialize<'de>"
+)))]
+pub struct Cursor<const D: usize, T: Size<D>> {
+    min: Z<D, T>,
+    max: Z<D, T>,
+    last: Option<Z<D, T>>,
+    skip: usize,
+    threshold: usize,
+    adaptive: bool,
+    interpolate: bool
+}
+
+impl<const D: usize, T: Size<D>> fmt::Debug for Cursor<D, T>
+where
+    <T as Size<D>>::Output: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .field("last", &self.last)
+            .field("skip", &self.skip)
+            .field("threshold", &self.threshold)
+            .field("adaptive", &self.adaptive)
+            .field("interpolate", &self.interpolate)
+            .finish()
+    }
+}
+
+impl<const D: usize, T: Size<D>> Cursor<D, T> {
+    /// Get the bounding box of the range search.
+    pub fn bbox(&self) -> Bbox<D, T> {
+        Bbox::new(self.min, self.max)
+    }
+
+    /// Get the `Z` value of the last element returned, if any.
+    pub fn last(&self) -> Option<Z<D, T>> {
+        self.last
+    }
+}
+
+#[cfg(feature = "minicbor")]
+use minicbor::encode::{self, Encode, Encoder, Write};
+#[cfg(feature = "minicbor")]
+use minicbor::decode::{self, Decode, Decoder};
+
+#[cfg(feature = "minicbor")]
+impl<const D: usize, T: Size<D>, C> Encode<C> for Cursor<D, T>
+where
+    <T as Size<D>>::Output: Encode<C>
+{
+    fn encode<W>(&self, e: &mut Encoder<W>, ctx: &mut C) -> Result<(), encode::Error<W::Error>>
+    where
+        W: Write
+    {
+        e.array(7)?;
+        self.min.encode(e, ctx)?;
+        self.max.encode(e, ctx)?;
+        self.last.encode(e, ctx)?;
+        self.skip.encode(e, ctx)?;
+        self.threshold.encode(e, ctx)?;
+        self.adaptive.encode(e, ctx)?;
+        self.interpolate.encode(e, ctx)
+    }
+}
+
+#[cfg(feature = "minicbor")]
+impl<'b, const D: usize, T: Size<D>, C> Decode<'b, C> for Cursor<D, T>
+where
+    <T as Size<D>>::Output: Decode<'b, C>
+{
+    fn decode(d: &mut Decoder<'b>, ctx: &mut C) -> Result<Self, decode::Error> {
+        let p = d.position();
+        if d.array()? != Some(7) {
+            return Err(decode::Error::message("expected array of length 7").at(p))
         }
+        Ok(Cursor {
+            min: Z::decode(d, ctx)?,
+            max: Z::decode(d, ctx)?,
+            last: Option::decode(d, ctx)?,
+            skip: usize::decode(d, ctx)?,
+            threshold: usize::decode(d, ctx)?,
+            adaptive: bool::decode(d, ctx)?,
+            interpolate: bool::decode(d, ctx)?
+        })
     }
 }
@@ -0,0 +1,228 @@
+use alloc::collections::{btree_map, btree_set, BTreeMap, BTreeSet};
+use core::iter::FusedIterator;
+use core::ops::Bound;
+use crate::{Bbox, Size, Z};
+
+/// Iterate over the entries of a map whose keys are within a bounding box.
+///
+/// The map is scanned in ascending key order with [`BTreeMap::range`]. On
+/// encountering a key outside of the bounding box, the scan jumps ahead to
+/// the next possible match as given by `bigmin`. Iterating from the back
+/// jumps backwards using `litmax`.
+pub fn range_map<const D: usize, T, V>(map: &BTreeMap<Z<D, T>, V>, min: [T; D], max: [T; D]) -> MapRange<'_, D, T, V>
+where
+    T: Size<D>
+{
+    let bounds = Bounds::new(Bbox::new(min.into(), max.into()));
+    MapRange { map, range: bounds.range().map(|r| map.range(r)), bounds }
+}
+
+/// Iterate over the elements of a set within a bounding box.
+///
+/// Like [`range_map`], but for a [`BTreeSet`].
+pub fn range_set<const D: usize, T>(set: &BTreeSet<Z<D, T>>, min: [T; D], max: [T; D]) -> SetRange<'_, D, T>
+where
+    T: Size<D>
+{
+    let bounds = Bounds::new(Bbox::new(min.into(), max.into()));
+    SetRange { set, range: bounds.range().map(|r| set.range(r)), bounds }
+}
+
+type KeyRange<const D: usize, T> = (Bound<Z<D, T>>, Bound<Z<D, T>>);
+
+/// The bounding box and the remaining key range of a scan.
+#[derive(Clone)]
+struct Bounds<const D: usize, T: Size<D>> {
+    bbox: Bbox<D, T>,
+    lo: Bound<Z<D, T>>,
+    hi: Bound<Z<D, T>>
+}
+
+impl<const D: usize, T: Size<D>> Bounds<D, T> {
+    fn new(bbox: Bbox<D, T>) -> Self {
+        Bounds {
+            lo: Bound::Included(bbox.min()),
+            hi: Bound::Included(bbox.max()),
+            bbox
+        }
+    }
+
+    /// Get the remaining key range, unless it is empty.
+    fn range(&self) -> Option<KeyRange<D, T>> {
+        let nonempty = match (self.lo, self.hi) {
+            | (Bound::Included(a), Bound::Included(b)) => a <= b,
+            | (Bound::Included(a), Bound::Excluded(b)) => a < b,
+            | (Bound::Excluded(a), Bound::Included(b)) => a < b,
+            | (Bound::Excluded(a), Bound::Excluded(b)) => a < b,
+            | _                                        => true
+        };
+        nonempty.then_some((self.lo, self.hi))
+    }
+
+    /// Check a key taken from the front.
+    ///
+    /// Returns `true` if the key is within the bounding box. Otherwise the
+    /// range is restricted to the next possible match and `false` is returned.
+    fn front(&mut self, z: &Z<D, T>) -> bool {
+        if self.bbox.contains(z) {
+            self.lo = Bound::Excluded(*z);
+            true
+        } else {
+            self.lo = Bound::Included(self.bbox.bigmin(z));
+            false
+        }
+    }
+
+    /// Check a key taken from the back.
+    ///
+    /// Like [`Bounds::front`], but the range is restricted to the previous
+    /// possible match.
+    fn back(&mut self, z: &Z<D, T>) -> bool {
+        if self.bbox.contains(z) {
+            self.hi = Bound::Excluded(*z);
+            true
+        } else {
+            self.hi = Bound::Included(self.bbox.litmax(z));
+            false
+        }
+    }
+}
+
+/// Iterator over the entries of a [`BTreeMap`] within a bounding box.
+///
+/// Created by [`range_map`].
+pub struct MapRange<'a, const D: usize, T: Size<D>, V> {
+    map: &'a BTreeMap<Z<D, T>, V>,
+    range: Option<btree_map::Range<'a, Z<D, T>, V>>,
+    bounds: Bounds<D, T>
+}
+
+impl<const D: usize, T: Size<D>, V> Clone for MapRange<'_, D, T, V> {
+    fn clone(&self) -> Self {
+        MapRange { map: self.map, range: self.range.clone(), bounds: self.bounds.clone() }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, V> Iterator for MapRange<'a, D, T, V> {
+    type Item = (&'a Z<D, T>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (k, v) = self.range.as_mut()?.next()?;
+            if self.bounds.front(k) {
+                return Some((k, v))
+            }
+            self.range = self.bounds.range().map(|r| self.map.range(r))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> DoubleEndedIterator for MapRange<'_, D, T, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (k, v) = self.range.as_mut()?.next_back()?;
+            if self.bounds.back(k) {
+                return Some((k, v))
+            }
+            self.range = self.bounds.range().map(|r| self.map.range(r))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, V> FusedIterator for MapRange<'_, D, T, V> {}
+
+/// Iterator over the elements of a [`BTreeSet`] within a bounding box.
+///
+/// Created by [`range_set`].
+pub struct SetRange<'a, const D: usize, T: Size<D>> {
+    set: &'a BTreeSet<Z<D, T>>,
+    range: Option<btree_set::Range<'a, Z<D, T>>>,
+    bounds: Bounds<D, T>
+}
+
+impl<const D: usize, T: Size<D>> Clone for SetRange<'_, D, T> {
+    fn clone(&self) -> Self {
+        SetRange { set: self.set, range: self.range.clone(), bounds: self.bounds.clone() }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>> Iterator for SetRange<'a, D, T> {
+    type Item = &'a Z<D, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let k = self.range.as_mut()?.next()?;
+            if self.bounds.front(k) {
+                return Some(k)
+            }
+            self.range = self.bounds.range().map(|r| self.set.range(r))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>> DoubleEndedIterator for SetRange<'_, D, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let k = self.range.as_mut()?.next_back()?;
+            if self.bounds.back(k) {
+                return Some(k)
+            }
+            self.range = self.bounds.range().map(|r| self.set.range(r))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>> FusedIterator for SetRange<'_, D, T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::vec::Vec;
+    use crate::{search, Z};
+
+    #[test]
+    fn range_map() {
+        let map = (0 .. 5000)
+            .map(|i| (Z::from(rand::random::<[u8; 2]>()), i))
+            .collect::<BTreeMap<_, _>>();
+        let vec = map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).map(|(k, v)| (k, v)).collect::<Vec<_>>();
+            assert_eq!(expected, search::range_map(&map, min, max).collect::<Vec<_>>());
+            let mut res = search::range_map(&map, min, max).rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res);
+
+            let mut it = search::range_map(&map, min, max);
+            let mut front = Vec::new();
+            let mut back = Vec::new();
+            loop {
+                match (it.next(), it.next_back()) {
+                    | (None, None) => break,
+                    | (a, b) => {
+                        front.extend(a);
+                        back.extend(b)
+                    }
+                }
+            }
+            front.extend(back.into_iter().rev());
+            assert_eq!(expected, front)
+        }
+    }
+
+    #[test]
+    fn range_set() {
+        let set = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<BTreeSet<_>>();
+        let vec = set.iter().copied().collect::<Vec<_>>();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, search::range_set(&set, min, max).collect::<Vec<_>>())
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use alloc::collections::VecDeque;
+use alloc::{vec, vec::Vec};
+use core::iter::FusedIterator;
+use crate::{Bbox, Size, GetZ, Z};
+use super::Threshold;
+
+/// A static index of items in Eytzinger layout.
+///
+/// The items are stored in the order of a breadth-first traversal of a
+/// complete binary search tree, i.e. the children of the item at position
+/// `i` are at positions `2i + 1` and `2i + 2`. Compared to bisection of a
+/// sorted slice, the first levels of a search are close together in memory,
+/// which makes range searches over large numbers of items more cache-friendly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eytzinger<A> {
+    items: Vec<A>
+}
+
+impl<A> Eytzinger<A> {
+    /// Create an index from items sorted by their `Z` value.
+    pub fn new(sorted: Vec<A>) -> Self {
+        let mut sorted = sorted.into_iter().map(Some).collect::<Vec<_>>();
+        let mut items = Vec::with_capacity(sorted.len());
+        for i in order(sorted.len()) {
+            items.push(sorted[i].take().expect("every position is visited once"))
+        }
+        Eytzinger { items }
+    }
+
+    /// Get the number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Borrow the items in Eytzinger layout.
+    pub fn as_slice(&self) -> &[A] {
+        &self.items
+    }
+
+    /// Get back the items in ascending `Z` order.
+    pub fn into_sorted(self) -> Vec<A> {
+        let mut items = self.items.into_iter().map(Some).collect::<Vec<_>>();
+        let mut sorted = Vec::with_capacity(items.len());
+        let mut node = Node::first(items.len());
+        while let Some(i) = node {
+            sorted.push(items[i].take().expect("every position is visited once"));
+            node = Node::successor(i, items.len())
+        }
+        sorted
+    }
+
+    /// Iterate over the items within the bounding box.
+    ///
+    /// Like [`range`](super::range), the elements are returned in ascending
+    /// `Z` order, or in descending order when iterating from the back.
+    pub fn range<const D: usize, T>(&self, min: [T; D], max: [T; D]) -> EytzingerRange<'_, D, T, A>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>
+    {
+        let bbox = Bbox::new(min.into(), max.into());
+        let mut frames = VecDeque::new();
+        if !self.items.is_empty() {
+            frames.push_back(Frame::Node(0, bbox.min(), bbox.max()))
+        }
+        EytzingerRange { items: &self.items, frames, bbox, threshold: Threshold::default() }
+    }
+}
+
+/// Get the sorted positions of the items in Eytzinger layout.
+fn order(len: usize) -> Vec<usize> {
+    let mut positions = vec![0; len];
+    let mut node = Node::first(len);
+    let mut rank = 0;
+    while let Some(i) = node {
+        positions[i] = rank;
+        rank += 1;
+        node = Node::successor(i, len)
+    }
+    positions
+}
+
+/// Navigation within an implicit complete binary tree.
+struct Node;
+
+impl Node {
+    /// Get the leftmost node, if any.
+    fn first(len: usize) -> Option<usize> {
+        (len > 0).then(|| Self::leftmost(0, len))
+    }
+
+    fn leftmost(mut i: usize, len: usize) -> usize {
+        while 2 * i + 1 < len {
+            i = 2 * i + 1
+        }
+        i
+    }
+
+    /// Get the next node of an in-order traversal.
+    fn successor(i: usize, len: usize) -> Option<usize> {
+        if 2 * i + 2 < len {
+            return Some(Self::leftmost(2 * i + 2, len))
+        }
+        // Go up until we come from a left child.
+        let mut i = i;
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if i == 2 * parent + 1 {
+                return Some(parent)
+            }
+            i = parent
+        }
+        None
+    }
+
+    /// Get the number of nodes in the subtree of node `i`.
+    fn subtree_len(i: usize, len: usize) -> usize {
+        let (mut lo, mut hi, mut n) = (i, i, 0);
+        while lo < len {
+            n += hi.min(len - 1) - lo + 1;
+            lo = 2 * lo + 1;
+            hi = 2 * hi + 2
+        }
+        n
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Frame<const D: usize, T: Size<D>> {
+    /// The subtree of a node whose `Z` values are within `min ..= max`.
+    Node(usize, Z<D, T>, Z<D, T>),
+    /// The position and `Z` value of an item within the bounding box.
+    Item(usize, Z<D, T>)
+}
+
+/// Iterator over `Z` values of an [`Eytzinger`] index.
+///
+/// Created by [`Eytzinger::range`].
+pub struct EytzingerRange<'a, const D: usize, T: Size<D>, A> {
+    items: &'a [A],
+    frames: VecDeque<Frame<D, T>>,
+    bbox: Bbox<D, T>,
+    threshold: Threshold
+}
+
+impl<const D: usize, T: Size<D>, A> Clone for EytzingerRange<'_, D, T, A> {
+    fn clone(&self) -> Self {
+        EytzingerRange {
+            items: self.items,
+            frames: self.frames.clone(),
+            bbox: self.bbox.clone(),
+            threshold: self.threshold
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T>> EytzingerRange<'_, D, T, A> {
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`](super::Zrange::optimize_if_gt) for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Split the subtree of node `i`.
+    ///
+    /// Returns the left subtree, the node's item if it is within the bounding
+    /// box, and the right subtree, in this order.
+    fn split(&self, i: usize, min: Z<D, T>, max: Z<D, T>) -> [Option<Frame<D, T>>; 3] {
+        let len = self.items.len();
+        let (l, r) = (2 * i + 1, 2 * i + 2);
+        let node = |j: usize, min, max| (j < len).then_some(Frame::Node(j, min, max));
+        let z = *self.items[i].z();
+        if z < min {
+            [None, None, node(r, min, max)]
+        } else if z > max {
+            [node(l, min, max), None, None]
+        } else if self.bbox.contains(&z) {
+            [node(l, min, z), Some(Frame::Item(i, z)), node(r, z, max)]
+        } else {
+            let upper = if Node::subtree_len(r, len) > self.threshold.value {
+                node(r, self.bbox.bigmin(&z), max)
+            } else {
+                node(r, z, max)
+            };
+            let lower = if Node::subtree_len(l, len) > self.threshold.value {
+                node(l, min, self.bbox.litmax(&z))
+            } else {
+                node(l, min, z)
+            };
+            [lower, None, upper]
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A: GetZ<D, T>> Iterator for EytzingerRange<'a, D, T, A> {
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(frame) = self.frames.pop_front() {
+            match frame {
+                Frame::Item(i, _)        => return Some(&self.items[i]),
+                Frame::Node(i, min, max) => {
+                    for f in self.split(i, min, max).into_iter().rev().flatten() {
+                        self.frames.push_front(f)
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T>> DoubleEndedIterator for EytzingerRange<'_, D, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(frame) = self.frames.pop_back() {
+            match frame {
+                Frame::Item(i, _)        => return Some(&self.items[i]),
+                Frame::Node(i, min, max) => {
+                    for f in self.split(i, min, max).into_iter().flatten() {
+                        self.frames.push_back(f)
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T>> FusedIterator for EytzingerRange<'_, D, T, A> {}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::{Eytzinger, Node};
+
+    #[test]
+    fn layout() {
+        for len in 0 .. 100 {
+            let vec = (0 .. len).map(Z::<2, u8>::new).collect::<Vec<_>>();
+            let index = Eytzinger::new(vec.clone());
+            for (i, z) in index.as_slice().iter().enumerate() {
+                assert_eq!(Node::subtree_len(i, len as usize), {
+                    let mut n = 0;
+                    let mut stack = Vec::from([i]);
+                    while let Some(j) = stack.pop() {
+                        if j < len as usize {
+                            n += 1;
+                            stack.extend([2 * j + 1, 2 * j + 2])
+                        }
+                    }
+                    n
+                });
+                if 2 * i + 1 < index.len() {
+                    assert!(index.as_slice()[2 * i + 1] < *z)
+                }
+                if 2 * i + 2 < index.len() {
+                    assert!(index.as_slice()[2 * i + 2] > *z)
+                }
+            }
+            assert_eq!(vec, index.into_sorted())
+        }
+    }
+
+    #[test]
+    fn range() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+        let index = Eytzinger::new(vec.clone());
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, index.range(min, max).collect::<Vec<_>>());
+            let mut res = index.range(min, max).rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res)
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use core::iter::FusedIterator;
+use num_traits::zero;
+use crate::{Bbox, Size, GetZ, Z};
+use super::{ByZ, Cursor, Frame, Frames, Key, Threshold, Traversal};
+#[cfg(feature = "stats")]
+use super::Stats;
+
+/// Get the capacity a [`FixedSearcher`] needs to search `len` items.
+///
+/// The number of frames a range search keeps is bounded by the logarithm
+/// of the number of items searched.
+pub const fn capacity(len: usize) -> usize {
+    if len == 0 {
+        0
+    } else {
+        4 * (len.ilog2() as usize + 1) + 1
+    }
+}
+
+/// A reusable range search with fixed-capacity memory.
+///
+/// Instead of allocating, the frames of a range search are kept in an array
+/// of `N` elements. It can be used without the `alloc` feature. See
+/// [`capacity`] for the number of elements required to search a given number
+/// of items.
+#[cfg_attr(feature = "alloc", doc = "
+Apart from that it works like [`Searcher`](super::Searcher).")]
+#[derive(Clone)]
+pub struct FixedSearcher<const D: usize, T: Size<D>, const N: usize> {
+    traversal: Traversal<D, T, Ring<D, T, N>>
+}
+
+impl<const D: usize, T: Size<D>, const N: usize> FixedSearcher<D, T, N> {
+    /// Create a new searcher for the given bounding box.
+    pub fn new(min: [T; D], max: [T; D]) -> Self {
+        Self::from(Bbox::new(min.into(), max.into()))
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// During range search, litmax and bigmin will only be calculated and used
+    /// if the remaining number of elements is larger than the threshold value.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.traversal.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// The adapted threshold carries over to subsequent searches.
+    #[cfg_attr(feature = "alloc", doc = "See [`Zrange::optimize_adaptive`](super::Zrange::optimize_adaptive)
+    for details.")]
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.traversal.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Get the bounding box of this searcher.
+    pub fn bbox(&self) -> &Bbox<D, T> {
+        &self.traversal.bbox
+    }
+
+    /// Replace the bounding box used for subsequent searches.
+    pub fn set_bbox(&mut self, bbox: Bbox<D, T>) {
+        self.traversal.bbox = bbox
+    }
+
+    /// Iterate over the elements of `items` within the bounding box.
+    ///
+    /// Out of a sequence of values that are sorted by their `Z` value, get all
+    /// elements within the bounding box, in ascending `Z` order.
+    ///
+    /// # Panics
+    ///
+    /// If `N` is less than [`capacity`] of `items.len()`.
+    pub fn range<'s, 'a, A>(&'s mut self, items: &'a [A]) -> ZrangeFixed<'s, 'a, D, T, A, N>
+    where
+        A: GetZ<D, T>
+    {
+        self.search(items, ByZ)
+    }
+
+    /// Iterate over the elements of `items` within the bounding box.
+    ///
+    /// Like [`FixedSearcher::range`], but the `Z` value of each element is
+    /// computed by the given key function.
+    ///
+    /// # Panics
+    ///
+    /// If `N` is less than [`capacity`] of `items.len()`.
+    pub fn range_by_key<'s, 'a, A, F>(&'s mut self, items: &'a [A], key: F) -> ZrangeFixed<'s, 'a, D, T, A, N, F>
+    where
+        F: Fn(&A) -> Z<D, T>
+    {
+        self.search(items, key)
+    }
+
+    /// Resume a range search at the given cursor.
+    ///
+    /// The bounding box and the settings of this searcher are replaced by
+    /// those of the cursor.
+    #[cfg_attr(feature = "alloc", doc = "See [`resume`](super::resume) for details.")]
+    ///
+    /// # Panics
+    ///
+    /// If `N` is less than [`capacity`] of `items.len()`.
+    pub fn resume<'s, 'a, A>(&'s mut self, items: &'a [A], cursor: &Cursor<D, T>) -> ZrangeFixed<'s, 'a, D, T, A, N>
+    where
+        A: GetZ<D, T>
+    {
+        self.search(items, ByZ).resume(cursor)
+    }
+
+    /// Resume a range search by key at the given cursor.
+    ///
+    /// Like [`FixedSearcher::resume`], but the `Z` value of each element is
+    /// computed by the given key function.
+    ///
+    /// # Panics
+    ///
+    /// If `N` is less than [`capacity`] of `items.len()`.
+    pub fn resume_by_key<'s, 'a, A, F>(&'s mut self, items: &'a [A], cursor: &Cursor<D, T>, key: F) -> ZrangeFixed<'s, 'a, D, T, A, N, F>
+    where
+        F: Fn(&A) -> Z<D, T>
+    {
+        self.search(items, key).resume(cursor)
+    }
+
+    fn search<'s, 'a, A, K>(&'s mut self, items: &'a [A], key: K) -> ZrangeFixed<'s, 'a, D, T, A, N, K> {
+        assert!(N >= capacity(items.len()), "capacity {N} < {}", capacity(items.len()));
+        self.traversal.reset(items.len());
+        ZrangeFixed { items, traversal: &mut self.traversal, key }
+    }
+}
+
+impl<const D: usize, T: Size<D>, const N: usize> From<Bbox<D, T>> for FixedSearcher<D, T, N> {
+    fn from(bbox: Bbox<D, T>) -> Self {
+        FixedSearcher { traversal: Traversal::new(0, bbox, Threshold::default()) }
+    }
+}
+
+/// Iterator over `Z` values using fixed-capacity memory.
+///
+/// Created by [`FixedSearcher::range`]. Elements are returned in ascending
+/// `Z` order, or in descending order when iterating from the back. The
+/// lifetime `'s` is the one of the searcher and `'a` the one of the items.
+pub struct ZrangeFixed<'s, 'a, const D: usize, T: Size<D>, A, const N: usize, K = ByZ> {
+    items: &'a [A],
+    traversal: &'s mut Traversal<D, T, Ring<D, T, N>>,
+    key: K
+}
+
+impl<const D: usize, T: Size<D>, A, const N: usize, K> ZrangeFixed<'_, '_, D, T, A, N, K> {
+    fn resume(self, cursor: &Cursor<D, T>) -> Self
+    where
+        K: Key<D, T, A>
+    {
+        self.traversal.resume(self.items, cursor, &self.key);
+        self
+    }
+
+    /// Get a cursor to resume this range search later.
+    ///
+    /// The cursor points right after the last element returned by
+    /// [`Iterator::next`]. Elements taken from the back are not taken into
+    /// account.
+    pub fn cursor(&self) -> Cursor<D, T>
+    where
+        K: Key<D, T, A>
+    {
+        self.traversal.cursor(|i| self.key.key(&self.items[i]))
+    }
+
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.traversal.stats
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, const N: usize, K> Iterator for ZrangeFixed<'_, 'a, D, T, A, N, K>
+where
+    K: Key<D, T, A>
+{
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (items, key) = (self.items, &self.key);
+        self.traversal.next(|i| key.key(&items[i])).map(|i| &items[i])
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, const N: usize, K> DoubleEndedIterator for ZrangeFixed<'_, '_, D, T, A, N, K>
+where
+    K: Key<D, T, A>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (items, key) = (self.items, &self.key);
+        self.traversal.next_back(|i| key.key(&items[i])).map(|i| &items[i])
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, const N: usize, K: Key<D, T, A>> FusedIterator for ZrangeFixed<'_, '_, D, T, A, N, K> {}
+
+/// A fixed-capacity ring buffer of frames.
+#[derive(Clone)]
+struct Ring<const D: usize, T: Size<D>, const N: usize> {
+    frames: [Frame<D, T>; N],
+    head: usize,
+    len: usize
+}
+
+impl<const D: usize, T: Size<D>, const N: usize> Default for Ring<D, T, N> {
+    fn default() -> Self {
+        Ring { frames: [Frame::new(0, 0, Z::new(zero()), Z::new(zero())); N], head: 0, len: 0 }
+    }
+}
+
+impl<const D: usize, T: Size<D>, const N: usize> Frames<D, T> for Ring<D, T, N> {
+    fn push_front(&mut self, f: Frame<D, T>) {
+        assert!(self.len < N, "ring buffer capacity exceeded");
+        self.head = (self.head + N - 1) % N;
+        self.frames[self.head] = f;
+        self.len += 1
+    }
+
+    fn push_back(&mut self, f: Frame<D, T>) {
+        assert!(self.len < N, "ring buffer capacity exceeded");
+        self.frames[(self.head + self.len) % N] = f;
+        self.len += 1
+    }
+
+    fn pop_front(&mut self) -> Option<Frame<D, T>> {
+        if self.len == 0 {
+            return None
+        }
+        let f = self.frames[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(f)
+    }
+
+    fn pop_back(&mut self) -> Option<Frame<D, T>> {
+        if self.len == 0 {
+            return None
+        }
+        self.len -= 1;
+        Some(self.frames[(self.head + self.len) % N])
+    }
+
+    fn front_mut(&mut self) -> Option<&mut Frame<D, T>> {
+        (self.len > 0).then(|| &mut self.frames[self.head])
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0
+    }
+
+    #[cfg(feature = "stats")]
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::FixedSearcher;
+
+    #[test]
+    fn range() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        let mut searcher = FixedSearcher::<2, u8, { super::capacity(5000) }>::new([0, 0], [0, 0]);
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            searcher.set_bbox(crate::Bbox::new(min.into(), max.into()));
+            assert_eq!(expected, searcher.range(vec.as_slice()).collect::<Vec<_>>());
+            let mut res = searcher.range(vec.as_slice()).rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res)
+        }
+    }
+
+    #[test]
+    fn results() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        // The results of a search outlive the borrow of the searcher.
+        let mut searcher = FixedSearcher::<2, u8, { super::capacity(5000) }>::new([10, 20], [100, 200]);
+        let first = searcher.range(vec.as_slice()).collect::<Vec<_>>();
+        searcher.set_bbox(crate::Bbox::new([50, 0].into(), [255, 80].into()));
+        let second = searcher.range(vec.as_slice()).collect::<Vec<_>>();
+        assert_eq!(search::range(vec.as_slice(), [10, 20], [100, 200]).collect::<Vec<_>>(), first);
+        assert_eq!(search::range(vec.as_slice(), [50, 0], [255, 80]).collect::<Vec<_>>(), second)
+    }
+
+    #[test]
+    fn capacity() {
+        for len in [1, 2, 3, 7, 8, 100, 1023, 1024, 1025, 4000] {
+            let vec = (0 .. len as u16).map(Z::<2, u8>::new).collect::<Vec<_>>();
+            let mut searcher = FixedSearcher::<2, u8, 64>::new([0, 0], [255, 255]).optimize_if_gt(0);
+            let mut it = searcher.range(&vec[.. len]);
+            let mut n = 0;
+            while it.next().is_some() {
+                n += 1;
+                n += it.next_back().map(|_| 1).unwrap_or(0)
+            }
+            assert_eq!(len, n)
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::iter::FusedIterator;
+use crate::{Bbox, Size, GetZ, Z};
+use super::{ByZ, Deque, Key, Threshold, Traversal};
+
+/// Iterate over a bounding box of `Z` values in several sorted slices.
+///
+/// Every slice is searched like with [`range`](super::range) and the results
+/// are merged into a single sequence in ascending `Z` order. Elements with
+/// equal `Z` values are returned in the order of their slices (cf.
+/// [`Zmerge::dedup`]).
+pub fn range_merged<'a, const D: usize, T, A>(slices: &[&'a [A]], min: [T; D], max: [T; D]) -> Zmerge<'a, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    Zmerge::new(slices, Bbox::new(min.into(), max.into()), ByZ)
+}
+
+/// Iterate over a bounding box of `Z` values computed by a key function in several sorted slices.
+///
+/// Like [`range_merged`], but the `Z` value of each element is computed by
+/// the given key function.
+pub fn range_merged_by_key<'a, const D: usize, T, A, F>(slices: &[&'a [A]], min: [T; D], max: [T; D], key: F) -> Zmerge<'a, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    Zmerge::new(slices, Bbox::new(min.into(), max.into()), key)
+}
+
+/// Iterator over `Z` values of several sorted slices.
+///
+/// Created by [`range_merged`].
+pub struct Zmerge<'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    slices: Vec<&'a [A]>,
+    traversals: Vec<Traversal<D, T, Deque<D, T>>>,
+    /// The `Z` value, slice index and position of the next match of every
+    /// slice that is not exhausted.
+    heap: BinaryHeap<Reverse<(Z<D, T>, usize, usize)>>,
+    key: K,
+    /// Skip elements with the `Z` value of the last element returned.
+    dedup: bool,
+    last: Option<Z<D, T>>
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Zmerge<'_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Zmerge {
+            slices: self.slices.clone(),
+            traversals: self.traversals.clone(),
+            heap: self.heap.clone(),
+            key: self.key.clone(),
+            dedup: self.dedup,
+            last: self.last
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> Zmerge<'a, D, T, A, K> {
+    fn new(slices: &[&'a [A]], bbox: Bbox<D, T>, key: K) -> Self {
+        let mut this = Zmerge {
+            slices: slices.to_vec(),
+            traversals: Vec::with_capacity(slices.len()),
+            heap: BinaryHeap::with_capacity(slices.len()),
+            key,
+            dedup: false,
+            last: None
+        };
+        for (s, items) in slices.iter().enumerate() {
+            this.traversals.push(Traversal::new(items.len(), bbox.clone(), Threshold::default()));
+            this.advance(s)
+        }
+        this
+    }
+
+    /// Return only the first of several elements with equal `Z` values.
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    /// Find the next match of slice `s` and put it onto the heap.
+    fn advance(&mut self, s: usize) {
+        let (items, key) = (self.slices[s], &self.key);
+        if let Some(i) = self.traversals[s].next(|i| key.key(&items[i])) {
+            self.heap.push(Reverse((key.key(&items[i]), s, i)))
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> Iterator for Zmerge<'a, D, T, A, K> {
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Reverse((z, s, i)) = self.heap.pop()?;
+            self.advance(s);
+            if self.dedup && self.last == Some(z) {
+                continue
+            }
+            self.last = Some(z);
+            return Some(&self.slices[s][i])
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Zmerge<'_, D, T, A, K> {}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use crate::{search, Z};
+
+    #[test]
+    fn range_merged() {
+        let slices = (0 .. 10)
+            .map(|i| {
+                let mut vec = (0 .. 1000).map(|_| (Z::from(rand::random::<[u8; 2]>()), i)).collect::<Vec<_>>();
+                vec.sort_by_key(|(z, _)| *z);
+                vec
+            })
+            .collect::<Vec<_>>();
+        let slices = slices.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let mut all = slices.concat();
+        all.sort_by_key(|(z, _)| *z);
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let mut expected = search::range(all.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, search::range_merged(&slices, min, max).collect::<Vec<_>>());
+            let res = search::range_merged_by_key(&slices, min, max, |e: &(Z<2, u8>, i32)| e.0).count();
+            assert_eq!(expected.len(), res);
+            expected.dedup_by_key(|(z, _)| *z);
+            assert_eq!(expected, search::range_merged(&slices, min, max).dedup().collect::<Vec<_>>())
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use core::iter;
+use rayon::iter::ParallelIterator;
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use crate::{Bbox, Size, GetZ, Z};
+use super::{ByZ, Deque, Frames, Key, Threshold, Traversal};
+
+/// Iterate over a bounding box of `Z` values in parallel.
+///
+/// Like [`range`](super::range), but the search is split across threads by
+/// dividing the pending ranges of items. The same elements as with `range`
+/// are produced, though not necessarily in ascending `Z` order.
+pub fn par_range<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D]) -> ParZrange<'_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    ParZrange::new(items, Bbox::new(min.into(), max.into()), ByZ)
+}
+
+/// Iterate over a bounding box of `Z` values computed by a key function in parallel.
+///
+/// Like [`par_range`], but the `Z` value of each element is computed by the
+/// given key function.
+pub fn par_range_by_key<const D: usize, T, A, F>(items: &[A], min: [T; D], max: [T; D], key: F) -> ParZrange<'_, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    ParZrange::new(items, Bbox::new(min.into(), max.into()), key)
+}
+
+/// Parallel iterator over `Z` values.
+///
+/// Created by [`par_range`].
+pub struct ParZrange<'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    items: &'a [A],
+    bbox: Bbox<D, T>,
+    threshold: Threshold,
+    key: K
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K> ParZrange<'a, D, T, A, K> {
+    fn new(items: &'a [A], bbox: Bbox<D, T>, key: K) -> Self {
+        ParZrange { items, bbox, threshold: Threshold::default(), key }
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`](super::Zrange::optimize_if_gt) for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.threshold = Threshold::fixed(t);
+        self
+    }
+}
+
+impl<'a, const D: usize, T, A, K> ParallelIterator for ParZrange<'a, D, T, A, K>
+where
+    T: Size<D> + Send,
+    <T as Size<D>>::Output: Send,
+    A: Sync,
+    K: Key<D, T, A> + Send + Sync
+{
+    type Item = &'a A;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>
+    {
+        let producer = Producer {
+            items: self.items,
+            traversal: Traversal::new(self.items.len(), self.bbox, self.threshold),
+            key: &self.key
+        };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+/// Produces the elements of some of the pending ranges of a range search.
+struct Producer<'a, 'k, const D: usize, T: Size<D>, A, K> {
+    items: &'a [A],
+    traversal: Traversal<D, T, Deque<D, T>>,
+    key: &'k K
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> Producer<'_, '_, D, T, A, K> {
+    /// The minimum number of items in a range to split it further.
+    const MIN_LEN: usize = 1024;
+
+    /// Split the first range until there are at least two frames.
+    fn expand(&mut self) -> bool {
+        let (items, key) = (self.items, self.key);
+        while self.traversal.frames.len() == 1 {
+            match self.traversal.frames.pop_front() {
+                | Some(f) if f.end - f.start >= Self::MIN_LEN => {
+                    for f in self.traversal.split(f, |i| key.key(&items[i])).into_iter().flatten() {
+                        self.traversal.frames.push_back(f)
+                    }
+                }
+                | Some(f) => {
+                    self.traversal.frames.push_front(f);
+                    return false
+                }
+                | None => return false
+            }
+        }
+        self.traversal.frames.len() > 1
+    }
+}
+
+impl<'a, const D: usize, T, A, K> UnindexedProducer for Producer<'a, '_, D, T, A, K>
+where
+    T: Size<D> + Send,
+    <T as Size<D>>::Output: Send,
+    A: Sync,
+    K: Key<D, T, A> + Sync
+{
+    type Item = &'a A;
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        if !self.expand() {
+            return (self, None)
+        }
+        let n = self.traversal.frames.len();
+        let mut other = Traversal::<D, T, Deque<D, T>>::new(0, self.traversal.bbox.clone(), self.traversal.threshold);
+        for f in iter::from_fn(|| self.traversal.frames.pop_back()).take(n - n / 2) {
+            other.frames.push_front(f)
+        }
+        let other = Producer { items: self.items, traversal: other, key: self.key };
+        (self, Some(other))
+    }
+
+    fn fold_with<F>(mut self, folder: F) -> F
+    where
+        F: Folder<Self::Item>
+    {
+        let (items, key) = (self.items, self.key);
+        let iter = iter::from_fn(|| self.traversal.next(|i| key.key(&items[i])));
+        folder.consume_iter(iter.map(|i| &items[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use rayon::iter::ParallelIterator;
+    use crate::{search, Z};
+
+    #[test]
+    fn par_range() {
+        let mut vec = (0 .. 50_000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            let mut res = search::par_range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            res.sort_unstable();
+            assert_eq!(expected, res);
+            let res = search::par_range_by_key(vec.as_slice(), min, max, |z| *z).count();
+            assert_eq!(expected.len(), res)
+        }
+    }
+}
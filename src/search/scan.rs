@@ -0,0 +1,221 @@
+use core::iter::FusedIterator;
+use num_traits::ToPrimitive;
+use crate::{Bbox, Size, GetZ, Z};
+use super::{ByZ, Key};
+
+/// Get the elements of `items` within the bounding box by skip-scanning.
+///
+/// The items are scanned linearly instead of being bisected. If an element
+/// outside of the bounding box is found, the scan jumps ahead to the next
+/// possible match, as given by `bigmin`, using a galloping search. This
+/// can be faster than bisection if most items between the bounding box's
+/// minimum and maximum `Z` value are within it. See [`Strategy`] for choosing
+/// between both.
+#[cfg_attr(feature = "alloc", doc = "
+The results are the same as those of [`range`](super::range).")]
+pub fn scan<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D]) -> Zscan<'_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    Zscan::new(items, Bbox::new(min.into(), max.into()), ByZ)
+}
+
+/// Get the elements of `items` within the bounding box by skip-scanning.
+///
+/// Like [`scan`], but the `Z` value of each element is computed by the
+/// given key function.
+pub fn scan_by_key<const D: usize, T, A, F>(items: &[A], min: [T; D], max: [T; D], key: F) -> Zscan<'_, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    Zscan::new(items, Bbox::new(min.into(), max.into()), key)
+}
+
+/// A range search strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Choose one of the strategies below (cf. [`Strategy::select`]).
+    #[default]
+    Auto,
+    /// Recursive bisection of the items.
+    #[cfg_attr(feature = "alloc", doc = "See [`Zrange`](super::Zrange).")]
+    Bisect,
+    /// Linear scan with `bigmin` jumps (cf. [`Zscan`]).
+    SkipScan
+}
+
+impl Strategy {
+    /// The fraction of the `Z` values between the minimum and maximum of a
+    /// bounding box which have to be within it to prefer a skip-scan.
+    const DENSITY: f64 = 0.25;
+
+    /// Choose a strategy for the given bounding box.
+    ///
+    /// A skip-scan is chosen if the box contains a large part of the `Z`
+    /// values between its minimum and maximum, i.e. if it is split into few
+    /// parts along the curve. Other variants than [`Strategy::Auto`] are
+    /// returned unchanged.
+    pub fn select<const D: usize, T: Size<D>>(self, bbox: &Bbox<D, T>) -> Self {
+        if self != Strategy::Auto {
+            return self
+        }
+        let volume = bbox.min_parts()
+            .iter()
+            .zip(bbox.max_parts())
+            .map(|(min, max)| (*max - *min).to_f64().unwrap_or(f64::MAX) + 1.0)
+            .product::<f64>();
+        let span = (bbox.max().point - bbox.min().point).to_f64().unwrap_or(f64::MAX) + 1.0;
+        if volume >= span * Self::DENSITY {
+            Strategy::SkipScan
+        } else {
+            Strategy::Bisect
+        }
+    }
+}
+
+/// Iterator over `Z` values found by skip-scanning.
+///
+/// Created by [`scan`]. Elements are returned in ascending `Z` order, or in
+/// descending order when iterating from the back, where `litmax` is used to
+/// jump backwards.
+pub struct Zscan<'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    items: &'a [A],
+    bbox: Bbox<D, T>,
+    /// The start of the items not yet looked at.
+    front: usize,
+    /// The end of the items not yet looked at.
+    back: usize,
+    key: K
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Zscan<'_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Zscan {
+            items: self.items,
+            bbox: self.bbox.clone(),
+            front: self.front,
+            back: self.back,
+            key: self.key.clone()
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> Zscan<'a, D, T, A, K> {
+    pub(super) fn new(items: &'a [A], bbox: Bbox<D, T>, key: K) -> Self {
+        let front = items.partition_point(|a| key.key(a) < bbox.min());
+        let back  = front + items[front ..].partition_point(|a| key.key(a) <= bbox.max());
+        Zscan { items, bbox, front, back, key }
+    }
+
+    fn gallop(&self, lo: usize, hi: usize, z: Z<D, T>) -> usize {
+        gallop(self.items, lo, hi, z, &self.key)
+    }
+
+    /// Get the end of the indices in `lo .. hi` with a `Z` value not greater than `z`.
+    fn gallop_back(&self, lo: usize, mut hi: usize, z: Z<D, T>) -> usize {
+        let mut step = 1;
+        while step < hi - lo && self.key.key(&self.items[hi - step]) > z {
+            hi -= step;
+            step *= 2
+        }
+        let start = hi.saturating_sub(step).max(lo);
+        start + self.items[start .. hi].partition_point(|a| self.key.key(a) <= z)
+    }
+}
+
+/// Get the first index in `lo .. hi` with a `Z` value not less than `z`.
+///
+/// The distance to `lo` is doubled until the index is passed, followed by a
+/// binary search, so that nearby indices are found quickly.
+pub(super) fn gallop<const D: usize, T, A, K>(items: &[A], mut lo: usize, hi: usize, z: Z<D, T>, key: &K) -> usize
+where
+    T: Size<D>,
+    K: Key<D, T, A>
+{
+    let mut step = 1;
+    while step < hi - lo && key.key(&items[lo + step - 1]) < z {
+        lo += step;
+        step *= 2
+    }
+    let end = (lo + step).min(hi);
+    lo + items[lo .. end].partition_point(|a| key.key(a) < z)
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K> Iterator for Zscan<'a, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.front < self.back {
+            let item = &self.items[self.front];
+            let z = self.key.key(item);
+            if self.bbox.contains(&z) {
+                self.front += 1;
+                return Some(item)
+            }
+            self.front = self.gallop(self.front + 1, self.back, self.bbox.bigmin(&z))
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> DoubleEndedIterator for Zscan<'_, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.front < self.back {
+            let item = &self.items[self.back - 1];
+            let z = self.key.key(item);
+            if self.bbox.contains(&z) {
+                self.back -= 1;
+                return Some(item)
+            }
+            self.back = self.gallop_back(self.front, self.back - 1, self.bbox.litmax(&z))
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Zscan<'_, D, T, A, K> {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use std::vec::Vec;
+    use crate::{search, Bbox, Z};
+    use super::Strategy;
+
+    #[test]
+    fn scan() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, search::scan(vec.as_slice(), min, max).collect::<Vec<_>>());
+            let mut res = search::scan(vec.as_slice(), min, max).rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res);
+            assert_eq!(expected, search::range_with(vec.as_slice(), min, max, Strategy::Bisect).collect::<Vec<_>>())
+        }
+    }
+
+    #[test]
+    fn select() {
+        let bbox = Bbox::<2, u8>::new(Z::from([0, 0]), Z::from([15, 15]));
+        assert_eq!(Strategy::SkipScan, Strategy::Auto.select(&bbox));
+        let bbox = Bbox::<2, u8>::new(Z::from([127, 0]), Z::from([128, 255]));
+        assert_eq!(Strategy::Bisect, Strategy::Auto.select(&bbox));
+        let bbox = Bbox::<3, u8>::new(Z::from([0, 0, 0]), Z::from([15, 15, 15]));
+        assert_eq!(Strategy::SkipScan, Strategy::Auto.select(&bbox));
+        let bbox = Bbox::<3, u8>::new(Z::from([127, 0, 0]), Z::from([128, 255, 255]));
+        assert_eq!(Strategy::Bisect, Strategy::Auto.select(&bbox));
+        assert_eq!(Strategy::SkipScan, Strategy::SkipScan.select(&bbox))
+    }
+}
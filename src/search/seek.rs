@@ -0,0 +1,272 @@
+#[cfg(feature = "alloc")]
+use alloc::collections::{btree_map, btree_set, BTreeMap, BTreeSet};
+use core::iter::FusedIterator;
+use crate::{Bbox, Size, Z};
+use super::{ByZ, Key};
+use super::scan::gallop;
+
+/// A position within items stored in ascending `Z` order.
+///
+/// This is all a storage backend needs to provide for [`seek_range`], which
+/// skips over items outside of a bounding box by seeking to their `bigmin`.
+#[cfg_attr(feature = "alloc", doc = "
+It is implemented for slices by [`SliceCursor`], and for the standard ordered
+collections by [`MapCursor`] and [`SetCursor`].")]
+pub trait ZCursor<const D: usize, T: Size<D>> {
+    /// The items returned by the cursor.
+    type Item;
+
+    /// Get the `Z` value of the current item, or `None` at the end.
+    fn key(&self) -> Option<Z<D, T>>;
+
+    /// Move to the first item with a `Z` value not less than `z`.
+    ///
+    /// A cursor is only ever moved forward. If the `Z` value of the current
+    /// item is not less than `z`, the cursor stays where it is.
+    fn seek(&mut self, z: Z<D, T>);
+
+    /// Take the current item and move to the next one.
+    fn next(&mut self) -> Option<Self::Item>;
+}
+
+/// Iterate over the items of a cursor within a bounding box.
+///
+/// The cursor is moved to the minimum of the bounding box and from there on
+/// to the `bigmin` of every item encountered outside of the bounding box.
+/// Items are returned in ascending `Z` order.
+pub fn seek_range<const D: usize, T, C>(cursor: C, min: [T; D], max: [T; D]) -> SeekRange<D, T, C>
+where
+    T: Size<D>,
+    C: ZCursor<D, T>
+{
+    let bbox = Bbox::new(min.into(), max.into());
+    let mut cursor = cursor;
+    cursor.seek(bbox.min());
+    SeekRange { cursor, bbox }
+}
+
+/// Iterator over the items of a [`ZCursor`] within a bounding box.
+///
+/// Created by [`seek_range`].
+#[derive(Clone)]
+pub struct SeekRange<const D: usize, T: Size<D>, C> {
+    cursor: C,
+    bbox: Bbox<D, T>
+}
+
+impl<const D: usize, T: Size<D>, C> SeekRange<D, T, C> {
+    /// Get back the cursor.
+    pub fn into_cursor(self) -> C {
+        self.cursor
+    }
+}
+
+impl<const D: usize, T: Size<D>, C: ZCursor<D, T>> Iterator for SeekRange<D, T, C> {
+    type Item = C::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let z = self.cursor.key()?;
+            if z > self.bbox.max() {
+                return None
+            }
+            if self.bbox.contains(&z) {
+                return self.cursor.next()
+            }
+            self.cursor.seek(self.bbox.bigmin(&z))
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, C: ZCursor<D, T>> FusedIterator for SeekRange<D, T, C> {}
+
+/// A [`ZCursor`] over a slice of items sorted by their `Z` value.
+///
+/// Seeking uses a galloping search from the current position.
+pub struct SliceCursor<'a, A, K = ByZ> {
+    items: &'a [A],
+    pos: usize,
+    key: K
+}
+
+impl<'a, A> SliceCursor<'a, A> {
+    /// Create a cursor at the start of the given items.
+    pub fn new(items: &'a [A]) -> Self {
+        SliceCursor { items, pos: 0, key: ByZ }
+    }
+}
+
+impl<'a, A, K> SliceCursor<'a, A, K> {
+    /// Create a cursor at the start of the given items.
+    ///
+    /// Like [`SliceCursor::new`], but the `Z` value of each element is
+    /// computed by the given key function.
+    pub fn by_key<const D: usize, T>(items: &'a [A], key: K) -> Self
+    where
+        T: Size<D>,
+        K: Fn(&A) -> Z<D, T>
+    {
+        SliceCursor { items, pos: 0, key }
+    }
+
+    /// Get the index of the current item.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<A, K: Clone> Clone for SliceCursor<'_, A, K> {
+    fn clone(&self) -> Self {
+        SliceCursor { items: self.items, pos: self.pos, key: self.key.clone() }
+    }
+}
+
+impl<'a, const D: usize, T, A, K> ZCursor<D, T> for SliceCursor<'a, A, K>
+where
+    T: Size<D>,
+    K: Key<D, T, A>
+{
+    type Item = &'a A;
+
+    fn key(&self) -> Option<Z<D, T>> {
+        self.items.get(self.pos).map(|a| self.key.key(a))
+    }
+
+    fn seek(&mut self, z: Z<D, T>) {
+        self.pos = gallop(self.items, self.pos, self.items.len(), z, &self.key)
+    }
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let a = self.items.get(self.pos)?;
+        self.pos += 1;
+        Some(a)
+    }
+}
+
+/// A [`ZCursor`] over the entries of a [`BTreeMap`].
+///
+/// Seeking starts a new [`BTreeMap::range`] at the target key.
+#[cfg(feature = "alloc")]
+pub struct MapCursor<'a, const D: usize, T: Size<D>, V> {
+    map: &'a BTreeMap<Z<D, T>, V>,
+    current: Option<(&'a Z<D, T>, &'a V)>,
+    rest: btree_map::Range<'a, Z<D, T>, V>
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, const D: usize, T: Size<D>, V> MapCursor<'a, D, T, V> {
+    /// Create a cursor at the first entry of the given map.
+    pub fn new(map: &'a BTreeMap<Z<D, T>, V>) -> Self {
+        let mut rest = map.range(..);
+        MapCursor { map, current: rest.next(), rest }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>, V> Clone for MapCursor<'_, D, T, V> {
+    fn clone(&self) -> Self {
+        MapCursor { map: self.map, current: self.current, rest: self.rest.clone() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, const D: usize, T: Size<D>, V> ZCursor<D, T> for MapCursor<'a, D, T, V> {
+    type Item = (&'a Z<D, T>, &'a V);
+
+    fn key(&self) -> Option<Z<D, T>> {
+        self.current.map(|(k, _)| *k)
+    }
+
+    fn seek(&mut self, z: Z<D, T>) {
+        if self.current.is_some_and(|(k, _)| *k < z) {
+            self.rest = self.map.range(z ..);
+            self.current = self.rest.next()
+        }
+    }
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let e = self.current.take()?;
+        self.current = self.rest.next();
+        Some(e)
+    }
+}
+
+/// A [`ZCursor`] over the elements of a [`BTreeSet`].
+///
+/// Seeking starts a new [`BTreeSet::range`] at the target value.
+#[cfg(feature = "alloc")]
+pub struct SetCursor<'a, const D: usize, T: Size<D>> {
+    set: &'a BTreeSet<Z<D, T>>,
+    current: Option<&'a Z<D, T>>,
+    rest: btree_set::Range<'a, Z<D, T>>
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, const D: usize, T: Size<D>> SetCursor<'a, D, T> {
+    /// Create a cursor at the first element of the given set.
+    pub fn new(set: &'a BTreeSet<Z<D, T>>) -> Self {
+        let mut rest = set.range(..);
+        SetCursor { set, current: rest.next(), rest }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const D: usize, T: Size<D>> Clone for SetCursor<'_, D, T> {
+    fn clone(&self) -> Self {
+        SetCursor { set: self.set, current: self.current, rest: self.rest.clone() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, const D: usize, T: Size<D>> ZCursor<D, T> for SetCursor<'a, D, T> {
+    type Item = &'a Z<D, T>;
+
+    fn key(&self) -> Option<Z<D, T>> {
+        self.current.copied()
+    }
+
+    fn seek(&mut self, z: Z<D, T>) {
+        if self.current.is_some_and(|k| *k < z) {
+            self.rest = self.set.range(z ..);
+            self.current = self.rest.next()
+        }
+    }
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let e = self.current.take()?;
+        self.current = self.rest.next();
+        Some(e)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::{MapCursor, SetCursor, SliceCursor};
+
+    #[test]
+    fn seek_range() {
+        let map = (0 .. 5000)
+            .map(|i| (Z::from(rand::random::<[u8; 2]>()), i))
+            .collect::<BTreeMap<_, _>>();
+        let set = map.keys().copied().collect::<BTreeSet<_>>();
+        let vec = map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            let res = search::seek_range(SliceCursor::new(vec.as_slice()), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let res = search::seek_range(SliceCursor::by_key(vec.as_slice(), |e: &(Z<2, u8>, i32)| e.0), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let expected = expected.into_iter().copied().collect::<Vec<_>>();
+            let res = search::seek_range(MapCursor::new(&map), min, max).map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let res = search::seek_range(SetCursor::new(&set), min, max).copied().collect::<Vec<_>>();
+            assert_eq!(expected.iter().map(|(z, _)| *z).collect::<Vec<_>>(), res)
+        }
+    }
+}
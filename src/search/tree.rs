@@ -0,0 +1,200 @@
+use alloc::collections::VecDeque;
+use core::iter::FusedIterator;
+use crate::{Bbox, Size, Z};
+
+/// A node of a binary search tree ordered by `Z` values.
+///
+/// All nodes in the left subtree of a node have smaller `Z` values and all
+/// nodes in the right subtree have larger `Z` values than the node itself.
+pub trait TreeNode<const D: usize, T: Size<D>> {
+    /// Get the `Z` value of this node.
+    fn key(&self) -> Z<D, T>;
+
+    /// Get the root of the left subtree, if any.
+    fn left(&self) -> Option<&Self>;
+
+    /// Get the root of the right subtree, if any.
+    fn right(&self) -> Option<&Self>;
+}
+
+/// Iterate over the nodes of a tree within a bounding box.
+///
+/// The tree is traversed like the items of a slice in [`range`](super::range),
+/// with each node taking the place of the midpoint: if a node is outside of
+/// the bounding box, its left subtree is only searched up to the node's
+/// `litmax` and its right subtree only from the node's `bigmin` onwards.
+/// Nodes are returned in ascending `Z` order, or in descending order when
+/// iterating from the back.
+pub fn range_tree<const D: usize, T, N>(root: Option<&N>, min: [T; D], max: [T; D]) -> TreeRange<'_, D, T, N>
+where
+    T: Size<D>,
+    N: TreeNode<D, T>
+{
+    let bbox = Bbox::new(min.into(), max.into());
+    let mut frames = VecDeque::new();
+    if let Some(n) = root {
+        frames.push_back(Frame::Node(n, bbox.min(), bbox.max()))
+    }
+    TreeRange { frames, bbox }
+}
+
+enum Frame<'a, const D: usize, T: Size<D>, N> {
+    /// A subtree whose nodes may match if their `Z` values are within `min ..= max`.
+    Node(&'a N, Z<D, T>, Z<D, T>),
+    /// A node within the bounding box.
+    Item(&'a N)
+}
+
+impl<const D: usize, T: Size<D>, N> Clone for Frame<'_, D, T, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<const D: usize, T: Size<D>, N> Copy for Frame<'_, D, T, N> {}
+
+/// Iterator over the nodes of a tree within a bounding box.
+///
+/// Created by [`range_tree`].
+pub struct TreeRange<'a, const D: usize, T: Size<D>, N> {
+    frames: VecDeque<Frame<'a, D, T, N>>,
+    bbox: Bbox<D, T>
+}
+
+impl<const D: usize, T: Size<D>, N> Clone for TreeRange<'_, D, T, N> {
+    fn clone(&self) -> Self {
+        TreeRange { frames: self.frames.clone(), bbox: self.bbox.clone() }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, N: TreeNode<D, T>> TreeRange<'a, D, T, N> {
+    /// Split the subtree of node `n`.
+    ///
+    /// Returns the left subtree, the node if it is within the bounding box,
+    /// and the right subtree, in this order.
+    fn split(&self, n: &'a N, min: Z<D, T>, max: Z<D, T>) -> [Option<Frame<'a, D, T, N>>; 3] {
+        let z = n.key();
+        if z < min {
+            [None, None, n.right().map(|r| Frame::Node(r, min, max))]
+        } else if z > max {
+            [n.left().map(|l| Frame::Node(l, min, max)), None, None]
+        } else if self.bbox.contains(&z) {
+            [n.left().map(|l| Frame::Node(l, min, z)), Some(Frame::Item(n)), n.right().map(|r| Frame::Node(r, z, max))]
+        } else {
+            let lower = n.left().map(|l| Frame::Node(l, min, self.bbox.litmax(&z)));
+            let upper = n.right().map(|r| Frame::Node(r, self.bbox.bigmin(&z), max));
+            [lower, None, upper]
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, N: TreeNode<D, T>> Iterator for TreeRange<'a, D, T, N> {
+    type Item = &'a N;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(frame) = self.frames.pop_front() {
+            match frame {
+                Frame::Item(n)           => return Some(n),
+                Frame::Node(n, min, max) => {
+                    for f in self.split(n, min, max).into_iter().rev().flatten() {
+                        self.frames.push_front(f)
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, N: TreeNode<D, T>> DoubleEndedIterator for TreeRange<'_, D, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(frame) = self.frames.pop_back() {
+            match frame {
+                Frame::Item(n)           => return Some(n),
+                Frame::Node(n, min, max) => {
+                    for f in self.split(n, min, max).into_iter().flatten() {
+                        self.frames.push_back(f)
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<const D: usize, T: Size<D>, N: TreeNode<D, T>> FusedIterator for TreeRange<'_, D, T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+    use std::vec::Vec;
+    use crate::{search, Z};
+    use super::TreeNode;
+
+    /// An unbalanced binary search tree.
+    struct Node {
+        z: Z<2, u8>,
+        left: Option<Box<Node>>,
+        right: Option<Box<Node>>
+    }
+
+    impl Node {
+        fn insert(link: &mut Option<Box<Node>>, z: Z<2, u8>) {
+            match link {
+                | None    => *link = Some(Box::new(Node { z, left: None, right: None })),
+                | Some(n) => Node::insert(if z < n.z { &mut n.left } else { &mut n.right }, z)
+            }
+        }
+    }
+
+    impl TreeNode<2, u8> for Node {
+        fn key(&self) -> Z<2, u8> {
+            self.z
+        }
+
+        fn left(&self) -> Option<&Self> {
+            self.left.as_deref()
+        }
+
+        fn right(&self) -> Option<&Self> {
+            self.right.as_deref()
+        }
+    }
+
+    #[test]
+    fn range_tree() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+        vec.dedup();
+        let mut root = None;
+        for i in order(vec.len()) {
+            Node::insert(&mut root, vec[i])
+        }
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(vec.as_slice(), min, max).copied().collect::<Vec<_>>();
+            let res = search::range_tree(root.as_deref(), min, max).map(|n| n.z).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let mut res = search::range_tree(root.as_deref(), min, max).rev().map(|n| n.z).collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res)
+        }
+        assert_eq!(0, search::range_tree::<2, u8, Node>(None, [0, 0], [255, 255]).count())
+    }
+
+    /// Get an insertion order of `len` sorted items that keeps the tree balanced.
+    fn order(len: usize) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut ranges = Vec::from([(0, len)]);
+        while let Some((start, end)) = ranges.pop() {
+            if start < end {
+                let mid = start + (end - start) / 2;
+                order.push(mid);
+                ranges.extend([(start, mid), (mid + 1, end)])
+            }
+        }
+        order
+    }
+}
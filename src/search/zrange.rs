@@ -0,0 +1,1245 @@
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::{Deref, Range};
+use crate::{Bbox, Periodic, Size, GetZ, Z};
+use super::{ByZ, Cursor, Deque, Key, Strategy, Threshold, Traversal, Zscan};
+#[cfg(feature = "stats")]
+use super::Stats;
+
+/// Iterate over a bounding box of `Z` values.
+///
+/// Out of a sequence of values that are sorted by their `Z` value, get all
+/// elements within a bounding box. The elements are returned in ascending
+/// `Z` order and the returned iterator is double-ended.
+pub fn range<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D]) -> Zrange<'_, '_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    Zrange::new(items, Bbox::new(min.into(), max.into()), Threshold::default(), ByZ)
+}
+
+/// Iterate over a bounding box of `Z` values computed by a key function.
+///
+/// Like [`range`], but instead of requiring a [`GetZ`] implementation, the
+/// `Z` value of each element is computed by the given key function. The items
+/// must be sorted by this key.
+pub fn range_by_key<const D: usize, T, A, F>(items: &[A], min: [T; D], max: [T; D], key: F) -> Zrange<'_, '_, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    Zrange::new(items, Bbox::new(min.into(), max.into()), Threshold::default(), key)
+}
+
+/// Iterate over a bounding box of `Z` values using the given strategy.
+///
+/// Like [`range`], but the elements are found by recursive bisection or by
+/// skip-scanning as given by the strategy. [`Strategy::Auto`] chooses based
+/// on the bounding box.
+pub fn range_with<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D], strategy: Strategy) -> Zsearch<'_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    let bbox = Bbox::new(min.into(), max.into());
+    match strategy.select(&bbox) {
+        | Strategy::SkipScan => Zsearch::SkipScan(Zscan::new(items, bbox, ByZ)),
+        | _                  => Zsearch::Bisect(Zrange::new(items, bbox, Threshold::default(), ByZ))
+    }
+}
+
+/// Iterate mutably over a bounding box of `Z` values.
+///
+/// Like [`range`], but returns mutable references to the elements within the
+/// bounding box.
+///
+/// It is a logic error to modify the `Z` value of an element through the
+/// returned references while the iterator is in use or before the next search
+/// on `items`, as `items` would no longer be sorted. The behaviour resulting
+/// from such a logic error is unspecified, but will not result in undefined
+/// behaviour.
+pub fn range_mut<const D: usize, T, A>(items: &mut [A], min: [T; D], max: [T; D]) -> ZrangeMut<'_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    ZrangeMut::new(items, Bbox::new(min.into(), max.into()), ByZ)
+}
+
+/// Iterate mutably over a bounding box of `Z` values computed by a key function.
+///
+/// Like [`range_mut`], but the `Z` value of each element is computed by the
+/// given key function (cf. [`range_by_key`]).
+pub fn range_mut_by_key<const D: usize, T, A, F>(items: &mut [A], min: [T; D], max: [T; D], key: F) -> ZrangeMut<'_, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    ZrangeMut::new(items, Bbox::new(min.into(), max.into()), key)
+}
+
+/// Resume a range search at the given cursor.
+///
+/// The search continues right after the last element that had been returned
+/// by the range search the cursor was obtained from (cf. [`Zrange::cursor`]).
+/// The `items` are expected to be the same as those of the original search,
+/// in which case the remaining elements are identical to those an
+/// uninterrupted range search would have returned. The litmax/bigmin threshold
+/// and interpolation are set as in the original search.
+pub fn resume<'a, const D: usize, T, A>(items: &'a [A], cursor: &Cursor<D, T>) -> Zrange<'a, 'a, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    Zrange::resume(items, cursor, ByZ)
+}
+
+/// Resume a range search by key at the given cursor.
+///
+/// Like [`resume`], but for range searches created with [`range_by_key`].
+pub fn resume_by_key<'a, const D: usize, T, A, F>(items: &'a [A], cursor: &Cursor<D, T>, key: F) -> Zrange<'a, 'a, D, T, A, F>
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    Zrange::resume(items, cursor, key)
+}
+
+/// Iterate over a periodic bounding box of `Z` values.
+///
+/// Like [`range`], except that a dimension with `min > max` is not normalised
+/// but denotes a range that wraps around, e.g. at the antimeridian. See
+/// [`Bbox::periodic`] for details. Every matching element is returned exactly
+/// once. The elements of each of the disjoint boxes are returned in ascending
+/// `Z` order, one box after another.
+pub fn range_periodic<const D: usize, T, A>(items: &[A], min: [T; D], max: [T; D]) -> ZrangePeriodic<'_, D, T, A>
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    ZrangePeriodic {
+        items,
+        boxes: Bbox::periodic(min, max),
+        current: None,
+        threshold: Threshold::default(),
+        #[cfg(feature = "stats")]
+        stats: Default::default()
+    }
+}
+
+/// Iterator over `Z` values.
+///
+/// Elements are returned in ascending `Z` order, or in descending order when
+/// iterating from the back.
+///
+/// The lifetime `'s` is the one of the [`Searcher`] the range search was
+/// started from, if any, and `'a` the one of the items.
+pub struct Zrange<'s, 'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    items: &'a [A],
+    traversal: Traversal<D, T, Deque<D, T>>,
+    key: K,
+    /// The traversal of the searcher, which is given back when done.
+    searcher: Option<&'s mut Traversal<D, T, Deque<D, T>>>
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Zrange<'_, '_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Zrange {
+            items: self.items,
+            traversal: self.traversal.clone(),
+            key: self.key.clone(),
+            searcher: None
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> Drop for Zrange<'_, '_, D, T, A, K> {
+    fn drop(&mut self) {
+        if let Some(traversal) = self.searcher.take() {
+            mem::swap(traversal, &mut self.traversal)
+        }
+    }
+}
+
+impl<'s, 'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> Zrange<'s, 'a, D, T, A, K> {
+    fn new(items: &'a [A], bbox: Bbox<D, T>, threshold: Threshold, key: K) -> Self {
+        Zrange {
+            items,
+            traversal: Traversal::new(items.len(), bbox, threshold),
+            key,
+            searcher: None
+        }
+    }
+
+    fn resume(items: &'a [A], cursor: &Cursor<D, T>, key: K) -> Self {
+        let mut traversal = Traversal::new(0, cursor.bbox(), Threshold::default());
+        traversal.resume(items, cursor, &key);
+        Zrange { items, traversal, key, searcher: None }
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// During range search, litmax and bigmin will only be calculated and used
+    /// if the remaining number of elements is larger than the threshold value.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.traversal.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// After each litmax or bigmin calculation, the threshold is halved if
+    /// the result allows skipping several elements and incremented otherwise.
+    /// This avoids tuning the threshold by hand if the distribution of the
+    /// data is not known in advance.
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.traversal.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Choose split points by interpolation instead of bisection.
+    ///
+    /// How many elements at either end of a range of elements lie before or
+    /// after the bounding box is estimated from the `Z` values of the
+    /// elements at, or right next to, both ends of the range. The element at
+    /// the estimated border is probed and, if it is outside as expected, the
+    /// range is split there. This reduces the number of comparisons on large,
+    /// uniformly distributed slices. If a probe lands on the wrong side of
+    /// the border, the range and all ranges split off from it are bisected,
+    /// so that skewed data costs only few extra comparisons.
+    pub fn interpolate(mut self) -> Self {
+        self.traversal.interpolate = true;
+        self
+    }
+
+    /// Get a cursor to resume this range search later (cf. [`resume`]).
+    ///
+    /// The cursor points right after the last element returned by
+    /// [`Iterator::next`]. Elements taken from the back are not taken into
+    /// account.
+    pub fn cursor(&self) -> Cursor<D, T> {
+        self.traversal.cursor(|i| self.key.key(&self.items[i]))
+    }
+
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.traversal.stats
+    }
+
+    /// Return the indices of matching elements instead of references.
+    pub fn indices(self) -> Indices<'s, 'a, D, T, A, K> {
+        Indices { range: self }
+    }
+
+    /// Return maximal index ranges of consecutive matching elements.
+    ///
+    /// Each range contains one or more adjacent indices of elements which are
+    /// within the bounding box. Ranges are separated by at least one element
+    /// outside of the bounding box.
+    pub fn runs(self) -> Runs<'s, 'a, D, T, A, K> {
+        Runs { range: self, pending: None }
+    }
+
+    /// Return maximal sub-slices of consecutive matching elements.
+    ///
+    /// Like [`Zrange::runs`], but returns the sub-slices of the items.
+    pub fn slices(self) -> Slices<'s, 'a, D, T, A, K> {
+        Slices { runs: self.runs() }
+    }
+
+    fn next_index(&mut self) -> Option<usize> {
+        let (items, key) = (self.items, &self.key);
+        self.traversal.next(|i| key.key(&items[i]))
+    }
+
+    fn next_back_index(&mut self) -> Option<usize> {
+        let (items, key) = (self.items, &self.key);
+        self.traversal.next_back(|i| key.key(&items[i]))
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K> Iterator for Zrange<'_, 'a, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_index().map(|i| &self.items[i])
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> DoubleEndedIterator for Zrange<'_, '_, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_back_index().map(|i| &self.items[i])
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Zrange<'_, '_, D, T, A, K> {}
+
+/// Iterator over `Z` values using one of several strategies.
+///
+/// Created by [`range_with`].
+pub enum Zsearch<'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    /// Search by recursive bisection.
+    Bisect(Zrange<'a, 'a, D, T, A, K>),
+    /// Search by skip-scanning.
+    SkipScan(Zscan<'a, D, T, A, K>)
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Zsearch<'_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        match self {
+            | Zsearch::Bisect(r)   => Zsearch::Bisect(r.clone()),
+            | Zsearch::SkipScan(s) => Zsearch::SkipScan(s.clone())
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K> Iterator for Zsearch<'a, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            | Zsearch::Bisect(r)   => r.next(),
+            | Zsearch::SkipScan(s) => s.next()
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> DoubleEndedIterator for Zsearch<'_, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            | Zsearch::Bisect(r)   => r.next_back(),
+            | Zsearch::SkipScan(s) => s.next_back()
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Zsearch<'_, D, T, A, K> {}
+
+/// A range search over owned items.
+///
+/// The items can be of any type that dereferences to a slice sorted by `Z`
+/// value, e.g. `Vec<A>` or `Arc<[A]>`. Iterating over a query by value yields
+/// clones of the matching items and does not borrow from anything, which
+/// allows returning the iterator from functions or sending it to other
+/// threads.
+#[derive(Clone)]
+pub struct Query<const D: usize, T: Size<D>, S> {
+    items: S,
+    bbox: Bbox<D, T>,
+    threshold: Threshold
+}
+
+impl<const D: usize, T: Size<D>, S> Query<D, T, S> {
+    /// Create a new query for elements of `items` within a bounding box.
+    pub fn new(items: S, min: [T; D], max: [T; D]) -> Self {
+        Query { items, bbox: Bbox::new(min.into(), max.into()), threshold: Threshold::default() }
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`] for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// See [`Zrange::optimize_adaptive`] for details.
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Borrow the items of this query.
+    pub fn items(&self) -> &S {
+        &self.items
+    }
+
+    /// Get the bounding box of this query.
+    pub fn bbox(&self) -> &Bbox<D, T> {
+        &self.bbox
+    }
+
+    /// Iterate over references to the elements within the bounding box.
+    pub fn iter<A>(&self) -> Zrange<'_, '_, D, T, A>
+    where
+        S: Deref<Target = [A]>,
+        A: GetZ<D, T>
+    {
+        Zrange::new(&self.items, self.bbox.clone(), self.threshold, ByZ)
+    }
+}
+
+impl<const D: usize, T: Size<D>, S, A> IntoIterator for Query<D, T, S>
+where
+    S: Deref<Target = [A]>,
+    A: GetZ<D, T> + Clone
+{
+    type Item = A;
+    type IntoIter = ZrangeOwned<D, T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        ZrangeOwned {
+            traversal: Traversal::new(self.items.len(), self.bbox, self.threshold),
+            items: self.items
+        }
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, S, A> IntoIterator for &'a Query<D, T, S>
+where
+    S: Deref<Target = [A]>,
+    A: GetZ<D, T> + 'a
+{
+    type Item = &'a A;
+    type IntoIter = Zrange<'a, 'a, D, T, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over owned `Z` values.
+///
+/// Created by consuming a [`Query`]. Elements are cloned and returned in
+/// ascending `Z` order, or in descending order when iterating from the back.
+#[derive(Clone)]
+pub struct ZrangeOwned<const D: usize, T: Size<D>, S> {
+    items: S,
+    traversal: Traversal<D, T, Deque<D, T>>
+}
+
+impl<const D: usize, T: Size<D>, S> ZrangeOwned<D, T, S> {
+    /// Get a cursor to resume this range search later (cf. [`resume`]).
+    ///
+    /// See [`Zrange::cursor`] for details.
+    pub fn cursor<A>(&self) -> Cursor<D, T>
+    where
+        S: Deref<Target = [A]>,
+        A: GetZ<D, T>
+    {
+        self.traversal.cursor(|i| *self.items[i].z())
+    }
+
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.traversal.stats
+    }
+
+    /// Get back the items.
+    pub fn into_items(self) -> S {
+        self.items
+    }
+}
+
+impl<const D: usize, T: Size<D>, S, A> Iterator for ZrangeOwned<D, T, S>
+where
+    S: Deref<Target = [A]>,
+    A: GetZ<D, T> + Clone
+{
+    type Item = A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let items = &*self.items;
+        self.traversal.next(|i| *items[i].z()).map(|i| items[i].clone())
+    }
+}
+
+impl<const D: usize, T: Size<D>, S, A> DoubleEndedIterator for ZrangeOwned<D, T, S>
+where
+    S: Deref<Target = [A]>,
+    A: GetZ<D, T> + Clone
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let items = &*self.items;
+        self.traversal.next_back(|i| *items[i].z()).map(|i| items[i].clone())
+    }
+}
+
+impl<const D: usize, T: Size<D>, S, A> FusedIterator for ZrangeOwned<D, T, S>
+where
+    S: Deref<Target = [A]>,
+    A: GetZ<D, T> + Clone
+{}
+
+/// Iterator over mutable `Z` values.
+///
+/// Created by [`range_mut`]. Elements are returned in ascending `Z` order, or
+/// in descending order when iterating from the back.
+pub struct ZrangeMut<'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    /// The items not yet returned from either end.
+    items: &'a mut [A],
+    /// The index of the first element of `items` in the original slice.
+    offset: usize,
+    traversal: Traversal<D, T, Deque<D, T>>,
+    key: K,
+    /// The `Z` value of the last element returned from the front.
+    last: Option<Z<D, T>>,
+    /// The number of elements returned from the front with a `Z` value of `last`.
+    skip: usize
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> ZrangeMut<'a, D, T, A, K> {
+    fn new(items: &'a mut [A], bbox: Bbox<D, T>, key: K) -> Self {
+        ZrangeMut {
+            traversal: Traversal::new(items.len(), bbox, Threshold::default()),
+            items,
+            offset: 0,
+            key,
+            last: None,
+            skip: 0
+        }
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`] for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.traversal.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// See [`Zrange::optimize_adaptive`] for details.
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.traversal.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Choose split points by interpolation instead of bisection.
+    ///
+    /// See [`Zrange::interpolate`] for details.
+    pub fn interpolate(mut self) -> Self {
+        self.traversal.interpolate = true;
+        self
+    }
+
+    /// Get a cursor to resume this range search later (cf. [`resume`]).
+    ///
+    /// See [`Zrange::cursor`] for details.
+    pub fn cursor(&self) -> Cursor<D, T> {
+        self.traversal.cursor_at(self.last, self.skip)
+    }
+
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.traversal.stats
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K> Iterator for ZrangeMut<'a, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    type Item = &'a mut A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (items, offset, key) = (&*self.items, self.offset, &self.key);
+        let i = self.traversal.next(|i| key.key(&items[i - offset]))?;
+        let (_, upper) = mem::take(&mut self.items).split_at_mut(i - offset);
+        let (item, upper) = upper.split_first_mut()?;
+        let z = self.key.key(item);
+        if self.last == Some(z) {
+            self.skip += 1
+        } else {
+            self.last = Some(z);
+            self.skip = 1
+        }
+        self.items  = upper;
+        self.offset = i + 1;
+        Some(item)
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> DoubleEndedIterator for ZrangeMut<'_, D, T, A, K>
+where
+    K: Key<D, T, A>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (items, offset, key) = (&*self.items, self.offset, &self.key);
+        let i = self.traversal.next_back(|i| key.key(&items[i - offset]))?;
+        let (lower, upper) = mem::take(&mut self.items).split_at_mut(i - offset);
+        self.items = lower;
+        upper.first_mut()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for ZrangeMut<'_, D, T, A, K> {}
+
+/// Iterator over indices of `Z` values.
+///
+/// Created by [`Zrange::indices`].
+pub struct Indices<'s, 'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    range: Zrange<'s, 'a, D, T, A, K>
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Indices<'_, '_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Indices { range: self.range.clone() }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> Indices<'_, '_, D, T, A, K> {
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.range.traversal.stats
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> Iterator for Indices<'_, '_, D, T, A, K> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next_index()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> DoubleEndedIterator for Indices<'_, '_, D, T, A, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back_index()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Indices<'_, '_, D, T, A, K> {}
+
+/// Iterator over index ranges of consecutive `Z` values.
+///
+/// Created by [`Zrange::runs`].
+pub struct Runs<'s, 'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    range: Zrange<'s, 'a, D, T, A, K>,
+    pending: Option<Range<usize>>
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Runs<'_, '_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Runs { range: self.range.clone(), pending: self.pending.clone() }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> Runs<'_, '_, D, T, A, K> {
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.range.traversal.stats
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> Iterator for Runs<'_, '_, D, T, A, K> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(i) = self.range.next_index() {
+            match &mut self.pending {
+                Some(r) if r.end == i => r.end += 1,
+                pending => {
+                    if let Some(r) = pending.replace(i .. i + 1) {
+                        return Some(r)
+                    }
+                }
+            }
+        }
+        self.pending.take()
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Runs<'_, '_, D, T, A, K> {}
+
+/// Iterator over sub-slices of consecutive `Z` values.
+///
+/// Created by [`Zrange::slices`].
+pub struct Slices<'s, 'a, const D: usize, T: Size<D>, A, K = ByZ> {
+    runs: Runs<'s, 'a, D, T, A, K>
+}
+
+impl<const D: usize, T: Size<D>, A, K: Clone> Clone for Slices<'_, '_, D, T, A, K> {
+    fn clone(&self) -> Self {
+        Slices { runs: self.runs.clone() }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K> Slices<'_, '_, D, T, A, K> {
+    /// Get the counters of this range search so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &Stats {
+        self.runs.stats()
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A, K: Key<D, T, A>> Iterator for Slices<'_, 'a, D, T, A, K> {
+    type Item = &'a [A];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let r = self.runs.next()?;
+        Some(&self.runs.range.items[r])
+    }
+}
+
+impl<const D: usize, T: Size<D>, A, K: Key<D, T, A>> FusedIterator for Slices<'_, '_, D, T, A, K> {}
+
+/// A reusable range search.
+///
+/// A searcher holds a bounding box and keeps the memory allocated by range
+/// searches, so that issuing many searches does not require allocating for
+/// each one. The bounding box can be changed between searches.
+#[derive(Clone)]
+pub struct Searcher<const D: usize, T: Size<D>> {
+    traversal: Traversal<D, T, Deque<D, T>>
+}
+
+impl<const D: usize, T: Size<D>> Searcher<D, T> {
+    /// Create a new searcher for the given bounding box.
+    pub fn new(min: [T; D], max: [T; D]) -> Self {
+        Self::from(Bbox::new(min.into(), max.into()))
+    }
+
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`] for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.traversal.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// The adapted threshold carries over to subsequent searches.
+    /// See [`Zrange::optimize_adaptive`] for details.
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.traversal.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Choose split points by interpolation instead of bisection.
+    ///
+    /// See [`Zrange::interpolate`] for details.
+    pub fn interpolate(mut self) -> Self {
+        self.traversal.interpolate = true;
+        self
+    }
+
+    /// Get the bounding box of this searcher.
+    pub fn bbox(&self) -> &Bbox<D, T> {
+        &self.traversal.bbox
+    }
+
+    /// Replace the bounding box used for subsequent searches.
+    pub fn set_bbox(&mut self, bbox: Bbox<D, T>) {
+        self.traversal.bbox = bbox
+    }
+
+    /// Iterate over the elements of `items` within the bounding box.
+    ///
+    /// Like [`range`], but reusing the memory of this searcher.
+    pub fn range<'s, 'a, A>(&'s mut self, items: &'a [A]) -> Zrange<'s, 'a, D, T, A>
+    where
+        A: GetZ<D, T>
+    {
+        self.search(items, ByZ)
+    }
+
+    /// Iterate over the elements of `items` within the bounding box.
+    ///
+    /// Like [`range_by_key`], but reusing the memory of this searcher.
+    pub fn range_by_key<'s, 'a, A, F>(&'s mut self, items: &'a [A], key: F) -> Zrange<'s, 'a, D, T, A, F>
+    where
+        F: Fn(&A) -> Z<D, T>
+    {
+        self.search(items, key)
+    }
+
+    fn search<'s, 'a, A, K>(&'s mut self, items: &'a [A], key: K) -> Zrange<'s, 'a, D, T, A, K> {
+        // Lend the traversal to the range search, keeping only the settings.
+        let mut lent = Traversal::new(0, self.traversal.bbox.clone(), self.traversal.threshold);
+        lent.interpolate = self.traversal.interpolate;
+        let mut traversal = mem::replace(&mut self.traversal, lent);
+        traversal.reset(items.len());
+        Zrange { items, traversal, key, searcher: Some(&mut self.traversal) }
+    }
+}
+
+impl<const D: usize, T: Size<D>> From<Bbox<D, T>> for Searcher<D, T> {
+    fn from(bbox: Bbox<D, T>) -> Self {
+        Searcher { traversal: Traversal::new(0, bbox, Threshold::default()) }
+    }
+}
+
+/// Iterator over `Z` values of a periodic bounding box.
+pub struct ZrangePeriodic<'a, const D: usize, T: Size<D>, A> {
+    items: &'a [A],
+    boxes: Periodic<D, T>,
+    current: Option<Zrange<'a, 'a, D, T, A>>,
+    threshold: Threshold,
+    /// The counters of the completed parts.
+    #[cfg(feature = "stats")]
+    stats: Stats
+}
+
+impl<const D: usize, T: Size<D>, A> Clone for ZrangePeriodic<'_, D, T, A> {
+    fn clone(&self) -> Self {
+        ZrangePeriodic {
+            items: self.items,
+            boxes: self.boxes.clone(),
+            current: self.current.clone(),
+            threshold: self.threshold,
+            #[cfg(feature = "stats")]
+            stats: self.stats
+        }
+    }
+}
+
+impl<const D: usize, T: Size<D>, A: GetZ<D, T>> ZrangePeriodic<'_, D, T, A> {
+    /// Set litmax/bigmin optimization threshold (default = 10).
+    ///
+    /// See [`Zrange::optimize_if_gt`] for details.
+    pub fn optimize_if_gt(mut self, t: usize) -> Self {
+        self.threshold = Threshold::fixed(t);
+        self
+    }
+
+    /// Adapt the litmax/bigmin optimization threshold during the search.
+    ///
+    /// See [`Zrange::optimize_adaptive`] for details.
+    pub fn optimize_adaptive(mut self) -> Self {
+        self.threshold = Threshold::adaptive();
+        self
+    }
+
+    /// Get the counters of this range search so far, summed over all parts.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.stats;
+        if let Some(r) = &self.current {
+            stats.merge(r.stats())
+        }
+        stats
+    }
+}
+
+impl<'a, const D: usize, T: Size<D>, A> Iterator for ZrangePeriodic<'a, D, T, A>
+where
+    A: GetZ<D, T>
+{
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(a) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(a)
+            }
+            #[cfg(feature = "stats")]
+            if let Some(r) = self.current.take() {
+                self.stats.merge(r.stats())
+            }
+            let bbox = self.boxes.next()?;
+            self.current = Some(Zrange::new(self.items, bbox, self.threshold, ByZ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+    use std::sync::Arc;
+    use crate::{Bbox, GetZ, Z};
+
+    #[test]
+    fn area() {
+        let mut vec = Vec::new();
+        for x in 0 .. 9u8 {
+            for y in 0 .. 17u8 {
+                vec.push(Z::from([y, x]));
+            }
+        }
+        vec.sort_unstable();
+
+        let mut res = (0 .. 129).map(Z::new).collect::<Vec<_>>();
+        res.extend([
+            129, 132, 133, 144, 145, 148, 149, 192, 193, 196, 197, 208,
+            209, 212, 213, 256, 258, 264, 266, 288, 290, 296, 298, 384
+        ].map(Z::new));
+
+        assert_eq!(&vec, &res);
+
+        let min = [5,  3];
+        let max = [10, 5];
+
+        let mut res = Vec::new();
+        for z in super::range(vec.as_slice(), min, max) {
+            res.push(z.point);
+        }
+        assert_eq! {
+            &[27, 30, 31, 49, 51, 52, 53, 54, 55, 74, 75, 78, 96, 97, 98, 99, 100, 102],
+            res.as_slice()
+        }
+    }
+
+    #[test]
+    fn ordered() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let bbox = Bbox::new(min.into(), max.into());
+            let expected = vec.iter().filter(|z| bbox.contains(z)).collect::<Vec<_>>();
+
+            let res = super::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+
+            let mut res = super::range(vec.as_slice(), min, max).rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res);
+
+            let res = super::range(vec.as_slice(), min, max).optimize_adaptive().collect::<Vec<_>>();
+            assert_eq!(expected, res);
+
+            let res = super::range(vec.as_slice(), min, max).interpolate().collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let mut res = super::range(vec.as_slice(), min, max).interpolate().rev().collect::<Vec<_>>();
+            res.reverse();
+            assert_eq!(expected, res);
+
+            let mut it = super::range(vec.as_slice(), min, max).optimize_if_gt(0);
+            let mut front = Vec::new();
+            let mut back: Vec<&Z<2, u8>> = Vec::new();
+            while let Some(z) = it.next() {
+                front.push(z);
+                back.extend(it.next_back())
+            }
+            front.extend(back.into_iter().rev());
+            assert_eq!(expected, front)
+        }
+    }
+
+    #[test]
+    fn resume() {
+        let mut vec = (0 .. 3000).map(|_| Z::from(rand::random::<[u8; 2]>().map(|n| n / 4))).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>().map(|n| n / 4);
+            let max = rand::random::<[u8; 2]>().map(|n| n / 4);
+            let expected = super::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+
+            let mut res: Vec<&Z<2, u8>> = Vec::new();
+            let mut it = super::range(vec.as_slice(), min, max);
+            loop {
+                let page = it.by_ref().take(7).collect::<Vec<_>>();
+                if page.is_empty() {
+                    break
+                }
+                res.extend(page);
+                it = super::resume(vec.as_slice(), &it.cursor())
+            }
+            assert_eq!(expected.len(), res.len());
+            assert!(expected.iter().zip(&res).all(|(a, b)| core::ptr::eq(*a, *b)))
+        }
+    }
+
+    /// Page through range searches, resuming each from a cursor passed
+    /// through `roundtrip`.
+    #[cfg(any(feature = "minicbor", feature = "ciborium"))]
+    fn resume_decoded(roundtrip: impl Fn(&super::Cursor<2, u16>) -> super::Cursor<2, u16>) {
+        let mut vec = (0 .. 20_000).map(|_| Z::from(rand::random::<[u16; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 20 {
+            let min = rand::random::<[u16; 2]>();
+            let max = rand::random::<[u16; 2]>();
+            let expected = super::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+
+            let mut res: Vec<&Z<2, u16>> = Vec::new();
+            let mut it = super::range(vec.as_slice(), min, max).optimize_adaptive().interpolate();
+            loop {
+                let page = it.by_ref().take(50).collect::<Vec<_>>();
+                if page.is_empty() {
+                    break
+                }
+                res.extend(page);
+                let cursor = roundtrip(&it.cursor());
+                assert_eq!(it.cursor(), cursor);
+                it = super::resume(vec.as_slice(), &cursor);
+                // The settings of the search are restored, too.
+                assert_eq!(cursor, it.cursor())
+            }
+            assert_eq!(expected, res)
+        }
+    }
+
+    #[cfg(feature = "minicbor")]
+    #[test]
+    fn resume_minicbor() {
+        resume_decoded(|c| minicbor::decode(&minicbor::to_vec(c).unwrap()).unwrap())
+    }
+
+    #[cfg(feature = "ciborium")]
+    #[test]
+    fn resume_serde() {
+        resume_decoded(|c| {
+            let mut buf = Vec::new();
+            ciborium::into_writer(c, &mut buf).unwrap();
+            ciborium::from_reader(buf.as_slice()).unwrap()
+        })
+    }
+
+    #[test]
+    fn indices_and_runs() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let bbox = Bbox::new(min.into(), max.into());
+            let expected = (0 .. vec.len()).filter(|i| bbox.contains(&vec[*i])).collect::<Vec<_>>();
+
+            let res = super::range(vec.as_slice(), min, max).indices().collect::<Vec<_>>();
+            assert_eq!(expected, res);
+
+            let runs = super::range(vec.as_slice(), min, max).runs().collect::<Vec<_>>();
+            assert!(runs.windows(2).all(|w| w[0].end < w[1].start));
+            assert_eq!(expected, runs.iter().cloned().flatten().collect::<Vec<_>>());
+
+            let slices = super::range(vec.as_slice(), min, max).slices().collect::<Vec<_>>();
+            assert_eq!(runs.len(), slices.len());
+            assert!(runs.into_iter().zip(slices).all(|(r, s)| &vec[r] == s))
+        }
+    }
+
+    #[test]
+    fn range_mut() {
+        struct Item(Z<2, u8>, usize);
+
+        impl GetZ<2, u8> for Item {
+            fn z(&self) -> &Z<2, u8> {
+                &self.0
+            }
+        }
+
+        let mut vec = (0 .. 5000).map(|_| Item(Z::from(rand::random::<[u8; 2]>()), 0)).collect::<Vec<_>>();
+        vec.sort_unstable_by_key(|a| a.0);
+
+        let mut expected = vec![0; vec.len()];
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let bbox = Bbox::new(min.into(), max.into());
+            for (n, a) in expected.iter_mut().zip(&vec) {
+                if bbox.contains(&a.0) {
+                    *n += 1
+                }
+            }
+            let mut it = super::range_mut(vec.as_mut_slice(), min, max);
+            while let Some(a) = it.next() {
+                a.1 += 1;
+                if let Some(b) = it.next_back() {
+                    b.1 += 1
+                }
+            }
+        }
+        assert!(expected.into_iter().zip(vec).all(|(n, a)| n == a.1))
+    }
+
+    #[test]
+    fn range_by_key() {
+        let mut vec = (0 .. 5000).map(|_| (rand::random::<[u8; 2]>(), ())).collect::<Vec<_>>();
+        vec.sort_unstable_by_key(|(p, _)| Z::from(p));
+        let pairs = vec.iter().map(|(p, v)| (Z::from(p), *v)).collect::<Vec<_>>();
+        let arcs = pairs.iter().copied().map(Arc::new).collect::<Vec<_>>();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = super::range(pairs.as_slice(), min, max).map(|(z, _)| z.deinterlace()).collect::<Vec<_>>();
+            let res = super::range_by_key(vec.as_slice(), min, max, |(p, _)| Z::from(p)).map(|(p, _)| *p).collect::<Vec<_>>();
+            assert_eq!(expected, res);
+            let res = super::range(arcs.as_slice(), min, max).map(|a| a.0.deinterlace()).collect::<Vec<_>>();
+            assert_eq!(expected, res)
+        }
+    }
+
+    #[test]
+    fn owned() {
+        fn query(items: Arc<[Z<2, u8>]>, min: [u8; 2], max: [u8; 2]) -> impl Iterator<Item = Z<2, u8>> + Send + 'static {
+            super::Query::new(items, min, max).into_iter()
+        }
+
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+        let items: Arc<[Z<2, u8>]> = vec.clone().into();
+
+        for _ in 0 .. 20 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = super::range(vec.as_slice(), min, max).copied().collect::<Vec<_>>();
+            let it = query(items.clone(), min, max);
+            let res = std::thread::spawn(move || it.collect::<Vec<_>>()).join().unwrap();
+            assert_eq!(expected, res);
+            let q = super::Query::new(vec.clone(), min, max);
+            assert_eq!(expected, (&q).into_iter().copied().collect::<Vec<_>>());
+            assert_eq!(expected, q.into_iter().collect::<Vec<_>>())
+        }
+    }
+
+    #[test]
+    fn searcher() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        let mut searcher = super::Searcher::new([0, 0], [0, 0]);
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = super::range(vec.as_slice(), min, max).collect::<Vec<_>>();
+            searcher.set_bbox(Bbox::new(min.into(), max.into()));
+            let mut it = searcher.range(vec.as_slice());
+            let n = expected.len() / 2;
+            let front = it.by_ref().take(n).collect::<Vec<_>>();
+            let fork = it.clone();
+            assert_eq!(expected[.. n], front);
+            assert_eq!(expected[n ..], it.collect::<Vec<_>>());
+            assert_eq!(expected[n ..], fork.collect::<Vec<_>>())
+        }
+    }
+
+    #[test]
+    fn searcher_results() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        // The results of a search outlive the borrow of the searcher.
+        let mut searcher = super::Searcher::new([10, 20], [100, 200]);
+        let first = searcher.range(vec.as_slice()).collect::<Vec<_>>();
+        searcher.set_bbox(Bbox::new([50, 0].into(), [255, 80].into()));
+        let second = searcher.range(vec.as_slice()).collect::<Vec<_>>();
+        assert_eq!(super::range(vec.as_slice(), [10, 20], [100, 200]).collect::<Vec<_>>(), first);
+        assert_eq!(super::range(vec.as_slice(), [50, 0], [255, 80]).collect::<Vec<_>>(), second)
+    }
+
+    #[test]
+    fn periodic() {
+        let mut vec = Vec::new();
+        for x in (0 ..= 255u8).step_by(3) {
+            for y in (0 ..= 255u8).step_by(5) {
+                vec.push(Z::from([x, y]));
+            }
+        }
+        vec.sort_unstable();
+
+        for (min, max) in [([250, 10], [20, 30]), ([10, 200], [30, 40]), ([240, 250], [9, 4]), ([3, 4], [50, 60])] {
+            let mut res = super::range_periodic(vec.as_slice(), min, max).copied().collect::<Vec<_>>();
+            res.sort_unstable();
+            let expected = vec.iter()
+                .copied()
+                .filter(|z| {
+                    z.deinterlace().into_iter().zip(min).zip(max).all(|((v, min), max)| {
+                        if min <= max { min <= v && v <= max } else { min <= v || v <= max }
+                    })
+                })
+                .collect::<Vec<_>>();
+            assert!(!expected.is_empty());
+            assert_eq!(expected, res)
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+
+            let mut it = super::range(vec.as_slice(), min, max);
+            let n = it.by_ref().count();
+            let stats = *it.stats();
+            assert_eq!(n, stats.items_matched);
+            // Items of frames within the bounding box are not compared.
+            assert!(stats.items_compared > 0);
+            assert!(stats.frames_pushed >= stats.max_depth);
+            assert!(stats.max_depth <= super::super::capacity(vec.len()));
+
+            let mut it = super::range(vec.as_slice(), min, max).optimize_if_gt(usize::MAX);
+            let m = it.by_ref().count();
+            assert_eq!(n, m);
+            assert_eq!(0, it.stats().litmax + it.stats().bigmin);
+            assert_eq!(0, it.stats().interpolations)
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn interpolate_stats() {
+        // Compare the total number of items compared with and without interpolation.
+        fn compared(vec: &[Z<2, u16>], boxes: &[([u16; 2], [u16; 2])]) -> [usize; 2] {
+            let mut sums = [0; 2];
+            for &(min, max) in boxes {
+                let mut it = super::range(vec, min, max);
+                let n = it.by_ref().count();
+                assert_eq!(0, it.stats().interpolations);
+                sums[0] += it.stats().items_compared;
+                let mut it = super::range(vec, min, max).interpolate();
+                assert_eq!(n, it.by_ref().count());
+                sums[1] += it.stats().items_compared
+            }
+            sums
+        }
+
+        let boxes = (0 .. 200)
+            .map(|_| {
+                let min = rand::random::<[u16; 2]>().map(|x| x % 60_000);
+                let size = rand::random::<[u16; 2]>().map(|x| x % 256);
+                (min, [min[0] + size[0], min[1] + size[1]])
+            })
+            .collect::<Vec<_>>();
+
+        let mut uniform = (0 .. 200_000).map(|_| Z::from(rand::random::<[u16; 2]>())).collect::<Vec<_>>();
+        uniform.sort_unstable();
+        let [bisected, interpolated] = compared(&uniform, &boxes);
+        assert!(interpolated * 10 < bisected * 9, "{interpolated} vs. {bisected}");
+
+        // Four dense clusters in a sparse space, searched with boxes around and within them.
+        let mut clustered = (0 .. 200_000)
+            .map(|_| {
+                let c = rand::random::<u16>() % 4 * 15_000;
+                Z::from(rand::random::<[u16; 2]>().map(|x| c + x % 64))
+            })
+            .collect::<Vec<_>>();
+        clustered.sort_unstable();
+        let within = (0 .. 200)
+            .map(|_| {
+                let c = rand::random::<u16>() % 4 * 15_000;
+                let min = rand::random::<[u16; 2]>().map(|x| c + x % 48);
+                let size = rand::random::<[u16; 2]>().map(|x| x % 16);
+                (min, [min[0] + size[0], min[1] + size[1]])
+            })
+            .collect::<Vec<_>>();
+        // Failed probes cost only a few extra comparisons per search.
+        for boxes in [boxes, within] {
+            let [bisected, interpolated] = compared(&clustered, &boxes);
+            assert!(interpolated < bisected + 4 * boxes.len(), "{interpolated} vs. {bisected}")
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Sorting by `Z` values.
+//!
+//! The radix sorts here do a fixed amount of work per item and byte of the
+//! `Z` values which actually differ, instead of comparing `Z` values.
+//! Whether this is faster than [`slice::sort_unstable`] depends on the
+//! number and size of the items and on the memory system, so compare them
+//! with the `sort` benchmarks.
+#![cfg_attr(feature = "std", doc = "
+With the `std` feature, [`ExternalSort`] sorts records which do not fit
+into memory by spilling sorted runs to temporary files.")]
+
+use alloc::vec::Vec;
+use core::mem;
+use num_traits::PrimInt;
+use crate::{Size, GetZ, Z};
+use crate::search::{ByZ, Key};
+
+#[cfg(feature = "std")]
+mod external;
+
+#[cfg(feature = "std")]
+pub use external::{Codec, ExternalError, ExternalMerge, ExternalSort, Records};
+
+#[cfg(all(feature = "std", feature = "minicbor"))]
+pub use external::Cbor;
+
+#[cfg(feature = "ciborium")]
+pub use external::SerdeCbor;
+
+/// The number of bits sorted per pass.
+const RADIX: u32 = 8;
+
+/// The number of buckets per pass.
+const BUCKETS: usize = 1 << RADIX;
+
+/// Sort items by their `Z` value with a radix sort.
+///
+/// The `Z` values are extracted once together with the index of their item.
+/// These pairs are distributed into buckets by the most significant byte of
+/// their `Z` value until a bucket fits into the cache. Each bucket is then
+/// sorted by a least significant digit radix sort on the remaining bytes.
+/// Bytes which are equal for all items are skipped. Finally the items are
+/// moved into place by swapping. The sort is stable and needs memory for
+/// two copies of the pairs.
+pub fn radix_sort<const D: usize, T, A>(items: &mut [A])
+where
+    T: Size<D>,
+    A: GetZ<D, T>
+{
+    sort(items, &ByZ)
+}
+
+/// Sort items by `Z` values computed by a key function with a radix sort.
+///
+/// Like [`radix_sort`], but the `Z` value of each element is computed by the
+/// given key function, which is called once per element.
+pub fn radix_sort_by_key<const D: usize, T, A, F>(items: &mut [A], key: F)
+where
+    T: Size<D>,
+    F: Fn(&A) -> Z<D, T>
+{
+    sort(items, &key)
+}
+
+fn sort<const D: usize, T, A, K>(items: &mut [A], key: &K)
+where
+    T: Size<D>,
+    K: Key<D, T, A>
+{
+    let mut entries = items.iter().enumerate().map(|(i, a)| (key.key(a).point, i)).collect::<Vec<_>>();
+    let mut buf = entries.clone();
+    let n = digits(&entries);
+    msd(&mut entries, &mut buf, n);
+    permute(items, &mut entries)
+}
+
+/// Move every item to its position among the sorted entries.
+///
+/// The item at position `p` is taken from the index of entry `p`. Each
+/// cycle of the permutation is followed by swapping items, and the indices
+/// of the entries are overwritten to mark them as done.
+fn permute<K, A>(items: &mut [A], entries: &mut [(K, usize)]) {
+    for start in 0 .. entries.len() {
+        let mut p = start;
+        while entries[p].1 != p {
+            let src = mem::replace(&mut entries[p].1, p);
+            if src == start {
+                break
+            }
+            items.swap(p, src);
+            p = src
+        }
+    }
+}
+
+/// Get the number of digits up to the most significant one that differs between keys.
+fn digits<K: PrimInt, A>(entries: &[(K, A)]) -> u32 {
+    let Some((first, _)) = entries.first() else {
+        return 0
+    };
+    let diff = entries.iter().fold(K::zero(), |d, (k, _)| d | (*k ^ *first));
+    let bits = K::zero().count_zeros() - diff.leading_zeros();
+    bits.div_ceil(RADIX)
+}
+
+fn digit<K: PrimInt>(k: K, d: u32) -> usize {
+    let mask = K::from(BUCKETS - 1).expect("digit mask fits into key");
+    ((k >> (d * RADIX) as usize) & mask).to_usize().expect("digit fits into usize")
+}
+
+/// Distribute entries into buckets by digit `d`.
+///
+/// The entries are moved from `src` to `dst`. Returns the bucket sizes,
+/// unless all entries fall into the same bucket, in which case nothing is
+/// moved.
+fn scatter<K: PrimInt, A: Copy>(src: &[(K, A)], dst: &mut [(K, A)], d: u32) -> Option<[usize; BUCKETS]> {
+    let mut counts = [0; BUCKETS];
+    for (k, _) in src {
+        counts[digit(*k, d)] += 1
+    }
+    if counts.contains(&src.len()) {
+        return None
+    }
+    let mut offsets = counts;
+    let mut sum = 0;
+    for o in &mut offsets {
+        sum += mem::replace(o, sum)
+    }
+    for e in src {
+        let b = digit(e.0, d);
+        dst[offsets[b]] = *e;
+        offsets[b] += 1
+    }
+    Some(counts)
+}
+
+/// Sort entries by their lowest `n` digits, most significant digit first.
+///
+/// Large inputs are distributed into buckets by their most significant
+/// digit, which are sorted recursively, so that the least significant digit
+/// passes operate on buckets that fit into the cache. `buf` is used as
+/// scratch space of the same length.
+fn msd<K: PrimInt, A: Copy>(entries: &mut [(K, A)], buf: &mut [(K, A)], n: u32) {
+    /// The maximum number of entries to sort least significant digit first.
+    const LSD_LEN: usize = 1 << 14;
+
+    if n == 0 {
+        return
+    }
+    if entries.len() <= LSD_LEN {
+        return lsd(entries, buf, n)
+    }
+    let Some(counts) = scatter(entries, buf, n - 1) else {
+        return msd(entries, buf, n - 1)
+    };
+    let mut start = 0;
+    for len in counts {
+        let r = start .. start + len;
+        msd(&mut buf[r.clone()], &mut entries[r], n - 1);
+        start += len
+    }
+    entries.copy_from_slice(buf)
+}
+
+/// Sort entries by their lowest `n` digits, least significant digit first.
+fn lsd<K: PrimInt, A: Copy>(entries: &mut [(K, A)], buf: &mut [(K, A)], n: u32) {
+    /// The minimum number of entries to radix sort.
+    const MIN_LEN: usize = 64;
+
+    if entries.len() < MIN_LEN {
+        entries.sort_by_key(|(k, _)| *k);
+        return
+    }
+    // Whether the entries sorted so far are in `buf`.
+    let mut swapped = false;
+    for d in 0 .. n {
+        let done = if swapped { scatter(buf, entries, d) } else { scatter(entries, buf, d) };
+        if done.is_some() {
+            swapped = !swapped
+        }
+    }
+    if swapped {
+        entries.copy_from_slice(buf)
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod par {
+    use alloc::vec::Vec;
+    use rayon::prelude::*;
+    use crate::{Size, GetZ, Z};
+    use crate::search::{ByZ, Key};
+    use super::{digits, msd, permute, scatter};
+
+    /// Sort items by their `Z` value with a parallel radix sort.
+    ///
+    /// Like [`radix_sort`](super::radix_sort), but the `Z` values are
+    /// extracted in parallel and, after distributing them by their most
+    /// significant differing byte, the buckets are sorted in parallel. The
+    /// items are moved into place sequentially.
+    pub fn par_radix_sort<const D: usize, T, A>(items: &mut [A])
+    where
+        T: Size<D> + Send,
+        <T as Size<D>>::Output: Send + Sync,
+        A: GetZ<D, T> + Sync
+    {
+        sort(items, &ByZ)
+    }
+
+    /// Sort items by `Z` values computed by a key function with a parallel radix sort.
+    ///
+    /// Like [`par_radix_sort`], but the `Z` value of each element is computed
+    /// by the given key function.
+    pub fn par_radix_sort_by_key<const D: usize, T, A, F>(items: &mut [A], key: F)
+    where
+        T: Size<D> + Send,
+        <T as Size<D>>::Output: Send + Sync,
+        A: Sync,
+        F: Fn(&A) -> Z<D, T> + Sync
+    {
+        sort(items, &key)
+    }
+
+    fn sort<const D: usize, T, A, K>(items: &mut [A], key: &K)
+    where
+        T: Size<D> + Send,
+        <T as Size<D>>::Output: Send + Sync,
+        A: Sync,
+        K: Key<D, T, A> + Sync
+    {
+        let mut entries = items.par_iter().enumerate().map(|(i, a)| (key.key(a).point, i)).collect::<Vec<_>>();
+        let mut buf = entries.clone();
+        let n = digits(&entries);
+        if n == 0 {
+            return
+        }
+        if let Some(counts) = scatter(&entries, &mut buf, n - 1) {
+            let mut buckets = Vec::with_capacity(counts.len());
+            let (mut src, mut dst) = (buf.as_mut_slice(), entries.as_mut_slice());
+            for len in counts {
+                let (a, s) = src.split_at_mut(len);
+                let (b, d) = dst.split_at_mut(len);
+                buckets.push((a, b));
+                (src, dst) = (s, d)
+            }
+            buckets.into_par_iter().for_each(|(a, b)| msd(a, b, n - 1));
+            permute(items, &mut buf)
+        } else {
+            msd(&mut entries, &mut buf, n - 1);
+            permute(items, &mut entries)
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+pub use par::{par_radix_sort, par_radix_sort_by_key};
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+    use std::vec::Vec;
+    use crate::Z;
+
+    #[test]
+    fn radix_sort() {
+        for len in [0, 1, 2, 100, 255, 256, 10_000, 100_000] {
+            // Items which are not `Copy` are moved into place.
+            let vec = (0 .. len).map(|i| (Z::from(rand::random::<[u16; 3]>()), i.to_string())).collect::<Vec<_>>();
+            let mut expected = vec.clone();
+            expected.sort_by_key(|(z, _)| *z);
+            let mut res = vec.clone();
+            super::radix_sort(&mut res);
+            assert_eq!(expected, res);
+
+            // Few distinct values with small coordinates check stability and skipped digits.
+            let vec = (0 .. len).map(|i| (Z::from(rand::random::<[u8; 3]>().map(|x| u32::from(x % 4))), i)).collect::<Vec<_>>();
+            let mut expected = vec.clone();
+            expected.sort_by_key(|(z, _)| *z);
+            let mut res = vec.clone();
+            super::radix_sort_by_key(&mut res, |(z, _)| *z);
+            assert_eq!(expected, res);
+
+            #[cfg(feature = "rayon")]
+            {
+                let mut res = vec.clone();
+                super::par_radix_sort(&mut res);
+                assert_eq!(expected, res);
+                let mut res = vec.clone();
+                super::par_radix_sort_by_key(&mut res, |(z, _)| *z);
+                assert_eq!(expected, res)
+            }
+        }
+    }
+}
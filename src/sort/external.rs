@@ -0,0 +1,484 @@
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use alloc::format;
+use alloc::vec::{self, Vec};
+use core::cmp::Reverse;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Range;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{Size, GetZ, Z};
+
+/// Encoding of records in memory and in temporary files.
+///
+/// With the `minicbor` feature, `Cbor` encodes records which implement
+/// `minicbor`'s `Encode` and `Decode`. With the `ciborium` feature,
+/// `SerdeCbor` encodes records which implement `serde`'s `Serialize` and
+/// `Deserialize`. Other formats can be used by implementing this trait.
+pub trait Codec<A> {
+    /// Append the encoding of a record to `buf`.
+    fn encode(&self, a: &A, buf: &mut Vec<u8>) -> Result<(), ExternalError>;
+
+    /// Decode a record from the bytes produced by [`Codec::encode`].
+    fn decode(&self, bytes: &[u8]) -> Result<A, ExternalError>;
+}
+
+/// Encode records as CBOR with [`minicbor`].
+#[cfg(feature = "minicbor")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cbor;
+
+#[cfg(feature = "minicbor")]
+impl<A: minicbor::Encode<()> + for<'b> minicbor::Decode<'b, ()>> Codec<A> for Cbor {
+    fn encode(&self, a: &A, buf: &mut Vec<u8>) -> Result<(), ExternalError> {
+        minicbor::encode(a, buf).map_err(|e| ExternalError::Encode(Box::new(e)))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<A, ExternalError> {
+        minicbor::decode(bytes).map_err(|e| ExternalError::Decode(Box::new(e)))
+    }
+}
+
+/// Encode records as CBOR with [`serde`] and [`ciborium`].
+#[cfg(feature = "ciborium")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SerdeCbor;
+
+#[cfg(feature = "ciborium")]
+impl<A: serde::Serialize + serde::de::DeserializeOwned> Codec<A> for SerdeCbor {
+    fn encode(&self, a: &A, buf: &mut Vec<u8>) -> Result<(), ExternalError> {
+        ciborium::into_writer(a, buf).map_err(|e| ExternalError::Encode(Box::new(e)))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<A, ExternalError> {
+        ciborium::from_reader(bytes).map_err(|e| ExternalError::Decode(Box::new(e)))
+    }
+}
+
+/// Sort records by their `Z` value with a bounded amount of memory.
+///
+/// Records are encoded with a [`Codec`] and collected in memory.
+/// Whenever the encoded records exceed the memory budget, they are sorted
+/// and written to a temporary file as a sorted run. Whenever `fanin` runs
+/// of the same level exist, they are merged into one run of the next level,
+/// which bounds the number of open files. Finally all runs are merged. The
+/// sort is stable and temporary files are removed when they are no longer
+/// needed.
+///
+/// Sorted records can be iterated over ([`ExternalSort::sort`]) or written
+/// to a file ([`ExternalSort::sort_to`]), which can be read back with
+/// [`Records`].
+#[derive(Debug, Clone)]
+pub struct ExternalSort<C> {
+    memory: usize,
+    fanin: usize,
+    dir: PathBuf,
+    codec: C
+}
+
+impl<C: Default> Default for ExternalSort<C> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+impl<C> ExternalSort<C> {
+    /// Create an external sort which encodes records with the given codec.
+    pub fn new(codec: C) -> Self {
+        ExternalSort {
+            memory: 64 * 1024 * 1024,
+            fanin: 16,
+            dir: std::env::temp_dir(),
+            codec
+        }
+    }
+
+    /// Set the approximate number of bytes of records kept in memory (default = 64 MiB).
+    ///
+    /// The budget covers the encoded records and their `Z` values.
+    pub fn memory(mut self, bytes: usize) -> Self {
+        self.memory = bytes;
+        self
+    }
+
+    /// Set the number of runs of the same level which are merged (default = 16).
+    pub fn fanin(mut self, n: usize) -> Self {
+        self.fanin = n.max(2);
+        self
+    }
+
+    /// Set the directory for temporary files (default = [`std::env::temp_dir`]).
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Sort records by their `Z` value.
+    ///
+    /// All records are consumed and sorted runs are written to temporary
+    /// files before this returns. The returned iterator merges the runs and
+    /// decodes the records in ascending `Z` order.
+    pub fn sort<const D: usize, T, A, I>(&self, records: I) -> Result<ExternalMerge<D, T, A, C>, ExternalError>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        C: Codec<A> + Clone,
+        I: IntoIterator<Item = A>
+    {
+        // The level and file of every run from oldest to newest.
+        let mut runs: Vec<(u32, TempPath)> = Vec::new();
+        let mut arena = Vec::new();
+        let mut index = Vec::new();
+        for a in records {
+            let start = arena.len();
+            self.codec.encode(&a, &mut arena)?;
+            index.push((*a.z(), start .. arena.len()));
+            if arena.len() + index.len() * mem::size_of::<(Z<D, T>, Range<usize>)>() >= self.memory {
+                index.sort_by_key(|(z, _)| *z);
+                runs.push((0, self.spill(&arena, &index)?));
+                arena.clear();
+                index.clear();
+                while runs.len() >= self.fanin {
+                    let n = runs.len();
+                    let level = runs[n - 1].0;
+                    if runs[n - self.fanin ..].iter().any(|(l, _)| *l != level) {
+                        break
+                    }
+                    let merged = runs.split_off(n - self.fanin).into_iter().map(|(_, p)| p);
+                    let (file, path) = TempPath::create(&self.dir)?;
+                    let sources = open(merged, &self.codec, Vec::new())?;
+                    write_records(ExternalMerge::new(sources, self.codec.clone())?, file, &self.codec)?;
+                    runs.push((level + 1, path))
+                }
+            }
+        }
+        index.sort_by_key(|(z, _)| *z);
+        let sources = Vec::with_capacity(runs.len() + 1);
+        let mut sources = open(runs.into_iter().map(|(_, p)| p), &self.codec, sources)?;
+        sources.push(Source::Memory(arena, index.into_iter()));
+        ExternalMerge::new(sources, self.codec.clone())
+    }
+
+    /// Sort records by their `Z` value and write them to `out`.
+    ///
+    /// Every record is written as its length in bytes (a little-endian
+    /// `u64`), followed by its encoding. Returns the number of records
+    /// written.
+    pub fn sort_to<const D: usize, T, A, I, W>(&self, records: I, out: W) -> Result<u64, ExternalError>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        C: Codec<A> + Clone,
+        I: IntoIterator<Item = A>,
+        W: Write
+    {
+        write_records(self.sort(records)?, out, &self.codec)
+    }
+
+    /// Write sorted records to a new temporary file.
+    fn spill<const D: usize, T: Size<D>>(&self, arena: &[u8], index: &[(Z<D, T>, Range<usize>)]) -> Result<TempPath, ExternalError> {
+        let (file, path) = TempPath::create(&self.dir)?;
+        let mut out = BufWriter::new(file);
+        for (_, r) in index {
+            write_record(&mut out, &arena[r.clone()])?
+        }
+        out.flush()?;
+        Ok(path)
+    }
+}
+
+/// Open the files of sorted runs.
+fn open<const D: usize, T: Size<D>, A, C, I>(runs: I, codec: &C, mut sources: Vec<Source<D, T, A, C>>) -> Result<Vec<Source<D, T, A, C>>, ExternalError>
+where
+    C: Codec<A> + Clone,
+    I: IntoIterator<Item = TempPath>
+{
+    for path in runs {
+        let records = Records::new(BufReader::new(File::open(&path.0)?), codec.clone());
+        sources.push(Source::Run { records, _path: path })
+    }
+    Ok(sources)
+}
+
+/// Write records and return their number.
+fn write_records<A, C, I, W>(records: I, out: W, codec: &C) -> Result<u64, ExternalError>
+where
+    C: Codec<A>,
+    I: IntoIterator<Item = Result<A, ExternalError>>,
+    W: Write
+{
+    let mut out = BufWriter::new(out);
+    let mut buf = Vec::new();
+    let mut n = 0;
+    for a in records {
+        buf.clear();
+        codec.encode(&a?, &mut buf)?;
+        write_record(&mut out, &buf)?;
+        n += 1
+    }
+    out.flush()?;
+    Ok(n)
+}
+
+fn write_record<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+/// A temporary file which is removed when dropped.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn create(dir: &Path) -> io::Result<(File, TempPath)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("zoc-{}-{n}.run", process::id()));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                | Ok(file)                                           => return Ok((file, TempPath(path))),
+                | Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                | Err(e)                                             => return Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Iterator over records written by [`ExternalSort::sort_to`].
+pub struct Records<R, A, C> {
+    reader: R,
+    codec: C,
+    buf: Vec<u8>,
+    _marker: PhantomData<fn() -> A>
+}
+
+impl<R: BufRead, A, C: Codec<A>> Records<R, A, C> {
+    /// Read records from the given reader and decode them with the codec
+    /// they were written with.
+    pub fn new(reader: R, codec: C) -> Self {
+        Records { reader, codec, buf: Vec::new(), _marker: PhantomData }
+    }
+
+    fn read(&mut self) -> Result<Option<A>, ExternalError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None)
+        }
+        let mut len = [0; 8];
+        self.reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        self.buf.clear();
+        if (&mut self.reader).take(len).read_to_end(&mut self.buf)? as u64 != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+        }
+        self.codec.decode(&self.buf).map(Some)
+    }
+}
+
+impl<R: BufRead, A, C: Codec<A>> Iterator for Records<R, A, C> {
+    type Item = Result<A, ExternalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Sorted records in memory or in a temporary file.
+enum Source<const D: usize, T: Size<D>, A, C> {
+    Memory(Vec<u8>, vec::IntoIter<(Z<D, T>, Range<usize>)>),
+    /// The file is removed after the records have been dropped.
+    Run { records: Records<BufReader<File>, A, C>, _path: TempPath }
+}
+
+impl<const D: usize, T: Size<D>, A, C: Codec<A>> Source<D, T, A, C> {
+    fn next(&mut self, codec: &C) -> Result<Option<A>, ExternalError> {
+        match self {
+            | Source::Memory(arena, index) => index.next().map(|(_, r)| codec.decode(&arena[r])).transpose(),
+            | Source::Run { records, .. }  => records.read()
+        }
+    }
+}
+
+/// Iterator over externally sorted records.
+///
+/// Created by [`ExternalSort::sort`]. After an error has been returned, the
+/// iterator is exhausted.
+pub struct ExternalMerge<const D: usize, T: Size<D>, A, C> {
+    sources: Vec<Source<D, T, A, C>>,
+    /// The next record of every source.
+    heads: Vec<Option<A>>,
+    /// The `Z` value of the next record and the index of every source that
+    /// is not exhausted.
+    heap: BinaryHeap<Reverse<(Z<D, T>, usize)>>,
+    error: Option<ExternalError>,
+    codec: C
+}
+
+impl<const D: usize, T, A, C> ExternalMerge<D, T, A, C>
+where
+    T: Size<D>,
+    A: GetZ<D, T>,
+    C: Codec<A>
+{
+    fn new(sources: Vec<Source<D, T, A, C>>, codec: C) -> Result<Self, ExternalError> {
+        let n = sources.len();
+        let mut this = ExternalMerge {
+            sources,
+            heads: (0 .. n).map(|_| None).collect(),
+            heap: BinaryHeap::with_capacity(n),
+            error: None,
+            codec
+        };
+        for s in 0 .. n {
+            this.advance(s)?
+        }
+        Ok(this)
+    }
+
+    /// Read the next record of source `s` and put it onto the heap.
+    fn advance(&mut self, s: usize) -> Result<(), ExternalError> {
+        if let Some(a) = self.sources[s].next(&self.codec)? {
+            self.heap.push(Reverse((*a.z(), s)));
+            self.heads[s] = Some(a)
+        }
+        Ok(())
+    }
+}
+
+impl<const D: usize, T, A, C> Iterator for ExternalMerge<D, T, A, C>
+where
+    T: Size<D>,
+    A: GetZ<D, T>,
+    C: Codec<A>
+{
+    type Item = Result<A, ExternalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            self.heap.clear();
+            self.sources.clear();
+            return Some(Err(e))
+        }
+        let Reverse((_, s)) = self.heap.pop()?;
+        let a = self.heads[s].take().expect("source on heap has a next record");
+        if let Err(e) = self.advance(s) {
+            self.error = Some(e)
+        }
+        Some(Ok(a))
+    }
+}
+
+impl<const D: usize, T, A, C> FusedIterator for ExternalMerge<D, T, A, C>
+where
+    T: Size<D>,
+    A: GetZ<D, T>,
+    C: Codec<A>
+{}
+
+/// Error returned by an [`ExternalSort`].
+#[derive(Debug)]
+pub enum ExternalError {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A record could not be encoded.
+    Encode(Box<dyn std::error::Error + Send + Sync>),
+    /// A record could not be decoded.
+    Decode(Box<dyn std::error::Error + Send + Sync>)
+}
+
+impl fmt::Display for ExternalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            | ExternalError::Io(e)     => write!(f, "i/o error: {e}"),
+            | ExternalError::Encode(e) => write!(f, "failed to encode record: {e}"),
+            | ExternalError::Decode(e) => write!(f, "failed to decode record: {e}")
+        }
+    }
+}
+
+impl std::error::Error for ExternalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            | ExternalError::Io(e)     => Some(e),
+            | ExternalError::Encode(e) => Some(&**e),
+            | ExternalError::Decode(e) => Some(&**e)
+        }
+    }
+}
+
+impl From<io::Error> for ExternalError {
+    fn from(e: io::Error) -> Self {
+        ExternalError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use crate::Z;
+    use super::{Codec, ExternalError, ExternalSort, Records};
+
+    /// Encode test records as their little-endian bytes.
+    #[derive(Clone)]
+    struct Bytes;
+
+    impl Codec<(Z<2, u8>, u32)> for Bytes {
+        fn encode(&self, (z, i): &(Z<2, u8>, u32), buf: &mut Vec<u8>) -> Result<(), ExternalError> {
+            buf.extend_from_slice(&z.point.to_le_bytes());
+            buf.extend_from_slice(&i.to_le_bytes());
+            Ok(())
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<(Z<2, u8>, u32), ExternalError> {
+            let bytes = <[u8; 6]>::try_from(bytes).map_err(|e| ExternalError::Decode(e.into()))?;
+            let z = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let i = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+            Ok((Z::new(z), i))
+        }
+    }
+
+    fn external_sort<C: Codec<(Z<2, u8>, u32)> + Clone>(codec: C) {
+        let vec = (0 .. 10_000u32).map(|i| (Z::from(rand::random::<[u8; 2]>()), i)).collect::<Vec<_>>();
+        let mut expected = vec.clone();
+        expected.sort_by_key(|(z, _)| *z);
+
+        for (memory, fanin) in [(0, 4), (1000, 16), (1 << 20, 16)] {
+            let sort = ExternalSort::new(codec.clone()).memory(memory).fanin(fanin);
+            let res = sort.sort(vec.iter().copied()).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(expected, res);
+
+            let mut out = Vec::new();
+            assert_eq!(vec.len() as u64, sort.sort_to(vec.iter().copied(), &mut out).unwrap());
+            let res = Records::new(out.as_slice(), codec.clone()).collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(expected, res)
+        }
+        assert_eq!(0, ExternalSort::new(codec).sort(Vec::new()).unwrap().count())
+    }
+
+    #[test]
+    fn external_sort_bytes() {
+        external_sort(Bytes)
+    }
+
+    #[cfg(feature = "minicbor")]
+    #[test]
+    fn external_sort_minicbor() {
+        external_sort(super::Cbor)
+    }
+
+    #[cfg(feature = "ciborium")]
+    #[test]
+    fn external_sort_serde() {
+        external_sort(super::SerdeCbor)
+    }
+}
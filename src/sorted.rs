@@ -0,0 +1,407 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use crate::{Size, GetZ, Z};
+use crate::search::{ByZ, FixedSearcher, Key, ZrangeFixed};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::search::{self, Searcher, Strategy, Zrange, ZrangeMut, Zsearch};
+
+/// Items which are known to be sorted by their `Z` value.
+///
+/// The range search functions in [`search`](crate::search) require sorted
+/// items and return wrong results otherwise. A `ZSorted` value can only be
+/// created by sorting or by checking the items, so searching it is always
+/// correct. The items can be accessed through [`Deref`], but not modified.
+///
+/// The `Z` values are those of the items' [`GetZ`] impl, or those computed by
+/// a key function `K` for values created by the `_by_key` constructors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZSorted<S, K = ByZ>(S, K);
+
+/// An owned vector of items sorted by their `Z` value.
+#[cfg(feature = "alloc")]
+pub type ZSortedVec<A, K = ByZ> = ZSorted<Vec<A>, K>;
+
+impl<S> ZSorted<S> {
+    /// Check that the given items are sorted by their `Z` value.
+    pub fn new<const D: usize, T, A>(items: S) -> Result<Self, NotSorted<S>>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        ZSorted::new_by_key(items, ByZ)
+    }
+
+    /// Sort the given items by their `Z` value.
+    ///
+    /// The sort is stable.
+    #[cfg(feature = "alloc")]
+    pub fn sort<const D: usize, T, A>(items: S) -> Self
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: DerefMut<Target = [A]>
+    {
+        ZSorted::sort_by_key(items, ByZ)
+    }
+
+    /// Sort the given items by their `Z` value.
+    ///
+    /// The sort is unstable and does not allocate.
+    #[cfg_attr(feature = "alloc", doc = "Otherwise like [`ZSorted::sort`].")]
+    pub fn sort_unstable<const D: usize, T, A>(items: S) -> Self
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: DerefMut<Target = [A]>
+    {
+        ZSorted::sort_unstable_by_key(items, ByZ)
+    }
+
+    /// Wrap items which are sorted by their `Z` value without checking.
+    ///
+    /// # Panics
+    ///
+    /// With debug assertions enabled, this panics with the position of the
+    /// first item out of order if the items are not sorted.
+    pub fn new_unchecked<const D: usize, T, A>(items: S) -> Self
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        debug_assert_sorted(&items);
+        ZSorted(items, ByZ)
+    }
+
+    /// Iterate over the items within the bounding box of a fixed-capacity searcher.
+    ///
+    /// See [`FixedSearcher::range`] for details.
+    pub fn range_fixed<'s, const D: usize, T, A, const N: usize>(&self, searcher: &'s mut FixedSearcher<D, T, N>) -> ZrangeFixed<'s, '_, D, T, A, N>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        searcher.range(&self.0)
+    }
+}
+
+impl<S, K> ZSorted<S, K> {
+    /// Check that the given items are sorted by the `Z` value computed by a
+    /// key function.
+    pub fn new_by_key<const D: usize, T, A>(items: S, key: K) -> Result<Self, NotSorted<S>>
+    where
+        T: Size<D>,
+        K: Key<D, T, A>,
+        S: Deref<Target = [A]>
+    {
+        match unsorted(&items, &key) {
+            | Some(position) => Err(NotSorted { position, items }),
+            | None           => Ok(ZSorted(items, key))
+        }
+    }
+
+    /// Sort the given items by the `Z` value computed by a key function.
+    ///
+    /// The sort is stable.
+    #[cfg(feature = "alloc")]
+    pub fn sort_by_key<const D: usize, T, A>(mut items: S, key: K) -> Self
+    where
+        T: Size<D>,
+        K: Key<D, T, A>,
+        S: DerefMut<Target = [A]>
+    {
+        items.sort_by_key(|a| key.key(a));
+        ZSorted(items, key)
+    }
+
+    /// Sort the given items by the `Z` value computed by a key function.
+    ///
+    /// The sort is unstable and does not allocate.
+    pub fn sort_unstable_by_key<const D: usize, T, A>(mut items: S, key: K) -> Self
+    where
+        T: Size<D>,
+        K: Key<D, T, A>,
+        S: DerefMut<Target = [A]>
+    {
+        items.sort_unstable_by_key(|a| key.key(a));
+        ZSorted(items, key)
+    }
+
+    /// Get back the items.
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+
+    /// Borrow the sorted items.
+    pub fn borrowed<A>(&self) -> ZSorted<&[A], K>
+    where
+        S: Deref<Target = [A]>,
+        K: Clone
+    {
+        ZSorted(&self.0, self.1.clone())
+    }
+
+    /// Iterate over the items within the bounding box of a fixed-capacity
+    /// searcher by their key.
+    ///
+    /// See [`FixedSearcher::range_by_key`] for details.
+    pub fn range_fixed_by_key<'s, const D: usize, T, A, const N: usize>(&self, searcher: &'s mut FixedSearcher<D, T, N>) -> ZrangeFixed<'s, '_, D, T, A, N, K>
+    where
+        T: Size<D>,
+        K: Fn(&A) -> Z<D, T> + Clone,
+        S: Deref<Target = [A]>
+    {
+        searcher.range_by_key(&self.0, self.1.clone())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S> ZSorted<S> {
+    /// Iterate over the items within the bounding box.
+    ///
+    /// See [`search::range`] for details.
+    pub fn range<const D: usize, T, A>(&self, min: [T; D], max: [T; D]) -> Zrange<'_, '_, D, T, A>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        search::range(&self.0, min, max)
+    }
+
+    /// Iterate over the items within the bounding box using the given strategy.
+    ///
+    /// See [`search::range_with`] for details.
+    pub fn range_with<const D: usize, T, A>(&self, min: [T; D], max: [T; D], strategy: Strategy) -> Zsearch<'_, D, T, A>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        search::range_with(&self.0, min, max, strategy)
+    }
+
+    /// Iterate mutably over the items within the bounding box.
+    ///
+    /// See [`search::range_mut`] for details. Modifying the `Z` value of an
+    /// item is a logic error, as the items would no longer be sorted.
+    pub fn range_mut<const D: usize, T, A>(&mut self, min: [T; D], max: [T; D]) -> ZrangeMut<'_, D, T, A>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: DerefMut<Target = [A]>
+    {
+        search::range_mut(&mut self.0, min, max)
+    }
+
+    /// Iterate over the items within the bounding box of a searcher.
+    ///
+    /// See [`Searcher::range`] for details.
+    pub fn range_in<'s, const D: usize, T, A>(&self, searcher: &'s mut Searcher<D, T>) -> Zrange<'s, '_, D, T, A>
+    where
+        T: Size<D>,
+        A: GetZ<D, T>,
+        S: Deref<Target = [A]>
+    {
+        searcher.range(&self.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S, F> ZSorted<S, F> {
+    /// Iterate over the items within the bounding box by their key.
+    ///
+    /// See [`search::range_by_key`] for details.
+    pub fn range_by_key<const D: usize, T, A>(&self, min: [T; D], max: [T; D]) -> Zrange<'_, '_, D, T, A, F>
+    where
+        T: Size<D>,
+        F: Fn(&A) -> Z<D, T> + Clone,
+        S: Deref<Target = [A]>
+    {
+        search::range_by_key(&self.0, min, max, self.1.clone())
+    }
+
+    /// Iterate mutably over the items within the bounding box by their key.
+    ///
+    /// See [`search::range_mut_by_key`] for details. Modifying the key of an
+    /// item is a logic error, as the items would no longer be sorted.
+    pub fn range_mut_by_key<const D: usize, T, A>(&mut self, min: [T; D], max: [T; D]) -> ZrangeMut<'_, D, T, A, F>
+    where
+        T: Size<D>,
+        F: Fn(&A) -> Z<D, T> + Clone,
+        S: DerefMut<Target = [A]>
+    {
+        search::range_mut_by_key(&mut self.0, min, max, self.1.clone())
+    }
+
+    /// Iterate over the items within the bounding box of a searcher by their key.
+    ///
+    /// See [`Searcher::range_by_key`] for details.
+    pub fn range_in_by_key<'s, const D: usize, T, A>(&self, searcher: &'s mut Searcher<D, T>) -> Zrange<'s, '_, D, T, A, F>
+    where
+        T: Size<D>,
+        F: Fn(&A) -> Z<D, T> + Clone,
+        S: Deref<Target = [A]>
+    {
+        searcher.range_by_key(&self.0, self.1.clone())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A, K> ZSorted<Vec<A>, K> {
+    /// Insert an item after all items with a `Z` value not greater than its own.
+    pub fn insert<const D: usize, T>(&mut self, item: A)
+    where
+        T: Size<D>,
+        K: Key<D, T, A>
+    {
+        let z = self.1.key(&item);
+        let i = self.0.partition_point(|a| self.1.key(a) <= z);
+        self.0.insert(i, item)
+    }
+
+    /// Remove the item at the given position.
+    pub fn remove(&mut self, i: usize) -> A {
+        self.0.remove(i)
+    }
+}
+
+impl<S: Deref, K> Deref for ZSorted<S, K> {
+    type Target = S::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Get the position of the first item with a smaller `Z` value than its predecessor.
+fn unsorted<const D: usize, T: Size<D>, A, K: Key<D, T, A>>(items: &[A], key: &K) -> Option<usize> {
+    items.windows(2).position(|w| key.key(&w[0]) > key.key(&w[1])).map(|i| i + 1)
+}
+
+/// Check that the given items are sorted by their `Z` value in debug builds.
+///
+/// # Panics
+///
+/// With debug assertions enabled, this panics with the position of the first
+/// item out of order if the items are not sorted. Otherwise it does nothing.
+pub fn debug_assert_sorted<const D: usize, T: Size<D>, A: GetZ<D, T>>(items: &[A]) {
+    if cfg!(debug_assertions) && let Some(i) = unsorted(items, &ByZ) {
+        panic!("items are not sorted by Z value: item {i} is less than its predecessor")
+    }
+}
+
+/// Error returned by [`ZSorted::new`] if the items are not sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotSorted<S> {
+    position: usize,
+    items: S
+}
+
+impl<S> NotSorted<S> {
+    /// Get the position of the first item with a smaller `Z` value than its predecessor.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Get back the items.
+    pub fn into_inner(self) -> S {
+        self.items
+    }
+}
+
+impl<S> fmt::Display for NotSorted<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "items are not sorted by Z value: item {} is less than its predecessor", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: fmt::Debug> std::error::Error for NotSorted<S> {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use std::vec::Vec;
+    use crate::{search, Bbox, Z};
+    use super::{ZSorted, ZSortedVec};
+
+    #[test]
+    fn sorted() {
+        let mut vec = (0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>();
+        vec.sort_unstable();
+        vec.push(Z::from([255, 255]));
+        vec.push(Z::from([0, 0]));
+        let err = ZSorted::new(vec.as_slice()).unwrap_err();
+        assert_eq!(vec.len() - 1, err.position());
+        vec.swap(0, 4000);
+        let mut sorted = ZSorted::sort(vec);
+        assert!(ZSorted::new(&*sorted).is_ok());
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(&sorted, min, max).count();
+            assert_eq!(expected, sorted.range(min, max).count());
+            assert_eq!(expected, sorted.borrowed().range_with(min, max, search::Strategy::SkipScan).count());
+            sorted.insert(Z::from(min))
+        }
+        let vec: ZSortedVec<_> = sorted;
+        assert!(ZSorted::new(vec.into_inner()).is_ok())
+    }
+
+    #[test]
+    fn sorted_by_key() {
+        let key = |e: &(u32, Z<2, u8>)| e.1;
+        let mut vec = (0 .. 5000).map(|i| (i, Z::from(rand::random::<[u8; 2]>()))).collect::<Vec<_>>();
+        assert!(ZSorted::new_by_key(vec.as_slice(), key).is_err());
+        vec.sort_by_key(key);
+        let sorted = ZSorted::new_by_key(vec.as_slice(), key).unwrap();
+        let mut unstable = ZSorted::sort_unstable_by_key(vec.clone(), key);
+        let mut searcher = search::Searcher::new([0, 0], [0, 0]);
+        let mut fixed = search::FixedSearcher::<2, u8, { search::capacity(5000) }>::new([0, 0], [0, 0]);
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range_by_key(vec.as_slice(), min, max, key).collect::<Vec<_>>();
+            assert_eq!(expected, sorted.range_by_key(min, max).collect::<Vec<_>>());
+            searcher.set_bbox(Bbox::new(min.into(), max.into()));
+            assert_eq!(expected, sorted.range_in_by_key(&mut searcher).collect::<Vec<_>>());
+            fixed.set_bbox(Bbox::new(min.into(), max.into()));
+            assert_eq!(expected, sorted.range_fixed_by_key(&mut fixed).collect::<Vec<_>>());
+            let n = unstable.range_mut_by_key(min, max).map(|e| e.0 = 0).count();
+            assert_eq!(expected.len(), n)
+        }
+    }
+
+    #[test]
+    fn search_variants() {
+        let mut sorted = ZSorted::sort_unstable((0 .. 5000).map(|_| Z::from(rand::random::<[u8; 2]>())).collect::<Vec<_>>());
+        let mut searcher = search::Searcher::new([0, 0], [0, 0]);
+        let mut fixed = search::FixedSearcher::<2, u8, { search::capacity(5000) }>::new([0, 0], [0, 0]);
+
+        for _ in 0 .. 100 {
+            let min = rand::random::<[u8; 2]>();
+            let max = rand::random::<[u8; 2]>();
+            let expected = search::range(&sorted, min, max).copied().collect::<Vec<_>>();
+            searcher.set_bbox(Bbox::new(min.into(), max.into()));
+            assert!(sorted.range_in(&mut searcher).eq(&expected));
+            fixed.set_bbox(Bbox::new(min.into(), max.into()));
+            assert!(sorted.range_fixed(&mut fixed).eq(&expected));
+            assert!(sorted.range_mut(min, max).map(|z| *z).eq(expected))
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "item 2 is less than its predecessor")]
+    fn unsorted() {
+        ZSorted::new_unchecked([Z::<2, u8>::from([0, 0]), Z::from([1, 1]), Z::from([0, 1])].as_slice());
+    }
+}
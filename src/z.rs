@@ -135,13 +135,79 @@ impl<const D: usize, T: Size<D>> Bbox<D, T> {
 
     /// Check if the given `z` is within this bounding box.
     pub fn contains(&self, z: &Z<D, T>) -> bool {
-        z.deinterlace().iter()
-            .zip(&self.min_parts)
-            .zip(&self.max_parts)
-            .all(|((z, min), max)| min <= z && z <= max)
+        // Compare the bits of each dimension without deinterlacing them.
+        let mask = T::max_value().expand();
+        (0 .. D).all(|d| {
+            let m = mask << d;
+            let x = z.point & m;
+            self.min.point & m <= x && x <= self.max.point & m
+        })
+    }
+
+    /// Check if all `Z` values from `min` to `max` are within this bounding
+    /// box, which is the case if the smallest aligned block containing both is.
+    pub(crate) fn covers(&self, min: &Z<D, T>, max: &Z<D, T>) -> bool {
+        let x = min.point ^ max.point;
+        let mask = if x == zero() { x } else { !zero::<<T as Size<D>>::Output>() >> x.leading_zeros() as usize };
+        self.contains(&Z::new(min.point & !mask)) && self.contains(&Z::new(max.point | mask))
+    }
+
+    /// Create bounding boxes for periodic dimensions.
+    ///
+    /// Unlike [`Bbox::new`], the arguments are not normalised. Instead, a
+    /// dimension with `min > max` denotes the wrapped interval from `min` to
+    /// `T::max_value()` followed by `0` to `max`. The result is the set of
+    /// disjoint bounding boxes covering the same area, i.e. up to 2^k boxes
+    /// for k wrapped dimensions.
+    pub fn periodic(min: [T; D], max: [T; D]) -> Periodic<D, T> {
+        let wrapped = min.iter().zip(&max).filter(|(min, max)| min > max).count();
+        Periodic { min, max, next: 0, count: 1 << wrapped }
+    }
+}
+
+/// Iterator over the disjoint bounding boxes of a periodic bounding box.
+///
+/// Created by [`Bbox::periodic`].
+#[derive(Debug, Clone)]
+pub struct Periodic<const D: usize, T> {
+    min: [T; D],
+    max: [T; D],
+    next: usize,
+    count: usize
+}
+
+impl<const D: usize, T: Size<D>> Iterator for Periodic<D, T> {
+    type Item = Bbox<D, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.count {
+            return None
+        }
+        let mut min = self.min;
+        let mut max = self.max;
+        let mut k = 0;
+        for (min, max) in min.iter_mut().zip(max.iter_mut()) {
+            if min > max {
+                if self.next & (1 << k) == 0 {
+                    *max = T::max_value()
+                } else {
+                    *min = zero()
+                }
+                k += 1
+            }
+        }
+        self.next += 1;
+        Some(Bbox::new(Z::interlace(&min), Z::interlace(&max)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.count - self.next;
+        (n, Some(n))
     }
 }
 
+impl<const D: usize, T: Size<D>> ExactSizeIterator for Periodic<D, T> {}
+
 /// A Z-order curve point.
 ///
 /// The point consists of the interleaved bits from all dimensions.
@@ -298,6 +364,7 @@ mod tests {
     use core::mem::size_of;
     use num_traits::zero;
     use rand::RngCore;
+    use std::vec;
     use crate::Size;
     use super::{bit, del_bit, set_bit, Bbox, F, T, Z};
 
@@ -591,4 +658,61 @@ mod tests {
 
         assert_bigmin::<2, u64>("D := 2, T := u64")
     }
+
+    #[test]
+    fn contains() {
+        fn assert_contains<const D: usize, T: Size<D>>(label: &str)
+        where
+            T: 'static,
+            <T as Size<D>>::Output: for<'a> Arbitrary<'a>
+        {
+            assert(label, |(a, b, c): (Z<D, T>, Z<D, T>, Z<D, T>)| {
+                let bbox = Bbox::new(b, c);
+                let inside = a.deinterlace().iter()
+                    .zip(bbox.min_parts())
+                    .zip(bbox.max_parts())
+                    .all(|((a, min), max)| min <= a && a <= max);
+                bbox.contains(&a) == inside
+            })
+        }
+
+        assert_contains::<2,  u8>("D := 2,  T := u8");
+        assert_contains::<3,  u8>("D := 3,  T := u8");
+        assert_contains::<5,  u8>("D := 5,  T := u8");
+        assert_contains::<16, u8>("D := 16, T := u8");
+
+        assert_contains::<2, u16>("D := 2, T := u16");
+        assert_contains::<3, u16>("D := 3, T := u16");
+
+        assert_contains::<2, u32>("D := 2, T := u32");
+
+        assert_contains::<2, u64>("D := 2, T := u64")
+    }
+
+    #[test]
+    fn covers() {
+        fn assert_covers<const D: usize, T: Size<D>>(label: &str)
+        where
+            T: 'static,
+            <T as Size<D>>::Output: for<'a> Arbitrary<'a>
+        {
+            assert(label, |(a, b, c, d): (Z<D, T>, Z<D, T>, Z<D, T>, Z<D, T>)| {
+                let bbox = Bbox::new(a, b);
+                let (min, max) = (c.min(d), c.max(d));
+                let mid = Z::new(min.point + ((max.point - min.point) >> 1));
+                !bbox.covers(&min, &max) || [min, mid, max].iter().all(|z| bbox.contains(z))
+            });
+            // A box of a single point covers that point.
+            assert(label, |z: Z<D, T>| Bbox::new(z, z).covers(&z, &z))
+        }
+
+        assert_covers::<2,  u8>("D := 2,  T := u8");
+        assert_covers::<3,  u8>("D := 3,  T := u8");
+        assert_covers::<5,  u8>("D := 5,  T := u8");
+
+        assert_covers::<2, u16>("D := 2, T := u16");
+        assert_covers::<3, u16>("D := 3, T := u16");
+
+        assert_covers::<2, u32>("D := 2, T := u32")
+    }
 }